@@ -99,6 +99,34 @@ fn validate_file(cmd: &ValidateCommand) -> Result<(), Box<dyn ::std::error::Erro
         &raw["httpserver"]["response"],
         codec::http::Response::sample(),
     )?;
+    assert(
+        &raw["httpserver"]["clientrequest"],
+        codec::http::ClientRequest::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["clientresponse"],
+        codec::http::ClientResponse::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["bodystreamstart"],
+        codec::http::BodyStreamStart::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["bodychunk"],
+        codec::http::BodyChunk::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["connection"],
+        codec::http::Connection::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["websocketmessage"],
+        codec::http::WebSocketMessage::sample(),
+    )?;
+    assert(
+        &raw["httpserver"]["serverevent"],
+        codec::http::ServerEvent::sample(),
+    )?;
     assert(
         &raw["blobstore"]["filechunk"],
         codec::blobstore::FileChunk::sample(),
@@ -107,6 +135,51 @@ fn validate_file(cmd: &ValidateCommand) -> Result<(), Box<dyn ::std::error::Erro
         &raw["blobstore"]["containerlist"],
         codec::blobstore::ContainerList::sample(),
     )?;
+    assert(
+        &raw["blobstore"]["streamrequest"],
+        codec::blobstore::StreamRequest::sample(),
+    )?;
+    assert(&raw["blobstore"]["blob"], codec::blobstore::Blob::sample())?;
+    assert(
+        &raw["blobstore"]["completeuploadrequest"],
+        codec::blobstore::CompleteUploadRequest::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["listobjectsrequest"],
+        codec::blobstore::ListObjectsRequest::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["listobjectsresponse"],
+        codec::blobstore::ListObjectsResponse::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["containerinfo"],
+        codec::blobstore::ContainerInfo::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["copyobjectrequest"],
+        codec::blobstore::CopyObjectRequest::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["streamcomplete"],
+        codec::blobstore::StreamComplete::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["streamerror"],
+        codec::blobstore::StreamError::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["ackchunks"],
+        codec::blobstore::AckChunks::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["presignedurlrequest"],
+        codec::blobstore::PresignedUrlRequest::sample(),
+    )?;
+    assert(
+        &raw["blobstore"]["presignedurlresponse"],
+        codec::blobstore::PresignedUrlResponse::sample(),
+    )?;
     assert(
         &raw["extras"]["result_guid"],
         codec::extras::GeneratorResult::sample(),
@@ -119,6 +192,42 @@ fn validate_file(cmd: &ValidateCommand) -> Result<(), Box<dyn ::std::error::Erro
         &raw["keyvalue"]["setrequest"],
         codec::keyvalue::SetRequest::sample(),
     )?;
+    assert(
+        &raw["keyvalue"]["addi64request"],
+        codec::keyvalue::AddI64Request::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["addf64request"],
+        codec::keyvalue::AddF64Request::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["keyexistsresponse"],
+        codec::keyvalue::KeyExistsResponse::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["listpopresponse"],
+        codec::keyvalue::ListPopResponse::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["hashsetrequest"],
+        codec::keyvalue::HashSetRequest::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["hashgetallresponse"],
+        codec::keyvalue::HashGetAllResponse::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["sortedsetaddrequest"],
+        codec::keyvalue::SortedSetAddRequest::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["sortedsetrangeresponse"],
+        codec::keyvalue::SortedSetRangeResponse::sample(),
+    )?;
+    assert(
+        &raw["keyvalue"]["transactionrequest"],
+        codec::keyvalue::TransactionRequest::sample(),
+    )?;
     assert(
         &raw["messaging"]["requestmessage"],
         codec::messaging::RequestMessage::sample(),
@@ -134,20 +243,48 @@ fn validate_file(cmd: &ValidateCommand) -> Result<(), Box<dyn ::std::error::Erro
 fn generate_httpserver_sample() -> serde_json::Value {
     json!({
         "request": base64::encode(codec::serialize(codec::http::Request::sample()).unwrap()),
-        "response": base64::encode(codec::serialize(codec::http::Response::sample()).unwrap())
+        "response": base64::encode(codec::serialize(codec::http::Response::sample()).unwrap()),
+        "clientrequest": base64::encode(codec::serialize(codec::http::ClientRequest::sample()).unwrap()),
+        "clientresponse": base64::encode(codec::serialize(codec::http::ClientResponse::sample()).unwrap()),
+        "bodystreamstart": base64::encode(codec::serialize(codec::http::BodyStreamStart::sample()).unwrap()),
+        "bodychunk": base64::encode(codec::serialize(codec::http::BodyChunk::sample()).unwrap()),
+        "connection": base64::encode(codec::serialize(codec::http::Connection::sample()).unwrap()),
+        "websocketmessage": base64::encode(codec::serialize(codec::http::WebSocketMessage::sample()).unwrap()),
+        "serverevent": base64::encode(codec::serialize(codec::http::ServerEvent::sample()).unwrap())
     })
 }
 
 fn generate_keyvalue_sample() -> serde_json::Value {
     json!({
-        "setrequest": base64::encode(codec::serialize(codec::keyvalue::SetRequest::sample()).unwrap())
+        "setrequest": base64::encode(codec::serialize(codec::keyvalue::SetRequest::sample()).unwrap()),
+        "addi64request": base64::encode(codec::serialize(codec::keyvalue::AddI64Request::sample()).unwrap()),
+        "addf64request": base64::encode(codec::serialize(codec::keyvalue::AddF64Request::sample()).unwrap()),
+        "keyexistsresponse": base64::encode(codec::serialize(codec::keyvalue::KeyExistsResponse::sample()).unwrap()),
+        "listpopresponse": base64::encode(codec::serialize(codec::keyvalue::ListPopResponse::sample()).unwrap()),
+        "hashsetrequest": base64::encode(codec::serialize(codec::keyvalue::HashSetRequest::sample()).unwrap()),
+        "hashgetallresponse": base64::encode(codec::serialize(codec::keyvalue::HashGetAllResponse::sample()).unwrap()),
+        "sortedsetaddrequest": base64::encode(codec::serialize(codec::keyvalue::SortedSetAddRequest::sample()).unwrap()),
+        "sortedsetrangeresponse": base64::encode(codec::serialize(codec::keyvalue::SortedSetRangeResponse::sample()).unwrap()),
+        "transactionrequest": base64::encode(codec::serialize(codec::keyvalue::TransactionRequest::sample()).unwrap())
     })
 }
 
 fn generate_blobstore_sample() -> serde_json::Value {
     json!({
         "filechunk": base64::encode(codec::serialize(codec::blobstore::FileChunk::sample()).unwrap()),
-        "containerlist": base64::encode(codec::serialize(codec::blobstore::ContainerList::sample()).unwrap())
+        "containerlist": base64::encode(codec::serialize(codec::blobstore::ContainerList::sample()).unwrap()),
+        "streamrequest": base64::encode(codec::serialize(codec::blobstore::StreamRequest::sample()).unwrap()),
+        "blob": base64::encode(codec::serialize(codec::blobstore::Blob::sample()).unwrap()),
+        "completeuploadrequest": base64::encode(codec::serialize(codec::blobstore::CompleteUploadRequest::sample()).unwrap()),
+        "listobjectsrequest": base64::encode(codec::serialize(codec::blobstore::ListObjectsRequest::sample()).unwrap()),
+        "listobjectsresponse": base64::encode(codec::serialize(codec::blobstore::ListObjectsResponse::sample()).unwrap()),
+        "containerinfo": base64::encode(codec::serialize(codec::blobstore::ContainerInfo::sample()).unwrap()),
+        "copyobjectrequest": base64::encode(codec::serialize(codec::blobstore::CopyObjectRequest::sample()).unwrap()),
+        "streamcomplete": base64::encode(codec::serialize(codec::blobstore::StreamComplete::sample()).unwrap()),
+        "streamerror": base64::encode(codec::serialize(codec::blobstore::StreamError::sample()).unwrap()),
+        "ackchunks": base64::encode(codec::serialize(codec::blobstore::AckChunks::sample()).unwrap()),
+        "presignedurlrequest": base64::encode(codec::serialize(codec::blobstore::PresignedUrlRequest::sample()).unwrap()),
+        "presignedurlresponse": base64::encode(codec::serialize(codec::blobstore::PresignedUrlResponse::sample()).unwrap())
     })
 }
 
@@ -5,33 +5,81 @@
 //! the documentation on [waSCC.dev](https://wascc.dev)
 
 use crate::Sample;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
 
-/// Guest sends a Container to the capability provider, receives a Container back
+/// Guest sends a Container to the capability provider, receives a ContainerInfo back. ContainerInfo
+/// shares its `id` field with Container, so guests may still decode the reply as a Container
 pub const OP_CREATE_CONTAINER: &str = "CreateContainer";
+/// Guest sends an empty payload to the capability provider, receives a ContainerList back
+pub const OP_LIST_CONTAINERS: &str = "ListContainers";
 /// Guest sends a Container to the capability provider, lack of error indicates success
 pub const OP_REMOVE_CONTAINER: &str = "RemoveContainer";
 /// Guest sends a Blob to the capability provider, lack of error indicates success
 pub const OP_REMOVE_OBJECT: &str = "RemoveObject";
 /// Guest sends a Container to the capability provider, receives a BlobList back
 pub const OP_LIST_OBJECTS: &str = "ListObjects";
-/// Guest sends a FileChunk to capability provider for storing as part of a Blob, lack of error indicates success
+/// Guest sends a ListObjectsRequest to the capability provider, receives a ListObjectsResponse containing
+/// a single page of results
+pub const OP_LIST_OBJECTS_PAGED: &str = "ListObjectsPaged";
+/// Guest sends a FileChunk to capability provider for storing as part of a Blob, lack of error indicates success.
+/// Providers must reject chunks whose `checksum` does not match their contents, and must verify the
+/// `blob_checksum` supplied at the start of the upload when the upload is completed with OP_COMPLETE_UPLOAD
 pub const OP_UPLOAD_CHUNK: &str = "UploadChunk";
 /// Guest sends a StreamRequest to the capability provider, immediate termination w/success. Guest will then
 /// start receiving OP_RECEIVE_CHUNK operations from the provider as chunks are streamed to the guest
 pub const OP_START_DOWNLOAD: &str = "StartDownload";
-/// Guest sends a metadata-carrying FileChunk to initiate an upload, lack of error is success
+/// Guest sends a metadata-carrying FileChunk to initiate an upload, lack of error is success. The chunk's
+/// `content_type`, `content_encoding`, and `metadata` fields are stored with the blob
 pub const OP_START_UPLOAD: &str = "StartUpload";
-/// Guest will receive a FileChunk for each piece of a file requested to download
+/// Guest will receive a FileChunk for each piece of a file requested to download. Each chunk's `offset`
+/// is the absolute position of its first byte within the blob, even when only a range was requested
 pub const OP_RECEIVE_CHUNK: &str = "ReceiveChunk";
-/// Query information on a single blob. Guest sends an incomplete blob struct and gets a complete one in return
+/// Guest will receive a StreamComplete after the last FileChunk of a download has been delivered
+pub const OP_DOWNLOAD_COMPLETE: &str = "DownloadComplete";
+/// Guest will receive a StreamError if the provider cannot continue a download. No further chunks are
+/// delivered for the download once this has been received
+pub const OP_DOWNLOAD_ERROR: &str = "DownloadError";
+/// Guest sends a CancelDownloadRequest to stop an in-flight download, lack of error indicates success.
+/// Chunks already dispatched by the provider may still arrive after the cancellation
+pub const OP_CANCEL_DOWNLOAD: &str = "CancelDownload";
+/// Guest sends AckChunks to grant the provider credit to deliver more chunks of a flow-controlled download,
+/// lack of error indicates success
+pub const OP_ACK_CHUNKS: &str = "AckChunks";
+/// Query information on a single blob. Guest sends an incomplete blob struct and gets a complete one in return,
+/// including content type, encoding, modification time, ETag and user metadata where the provider supports them
 pub const OP_GET_OBJECT_INFO: &str = "GetObjectInfo";
+/// Guest sends a CopyObjectRequest to the capability provider, receives the Blob for the new copy
+pub const OP_COPY_OBJECT: &str = "CopyObject";
+/// Guest sends a MoveObjectRequest to the capability provider, receives the Blob at its new location
+pub const OP_MOVE_OBJECT: &str = "MoveObject";
+/// Guest sends a PresignedUrlRequest to the capability provider, receives a PresignedUrlResponse. Providers
+/// whose storage cannot be accessed directly by clients (e.g. a local file system) return an error
+pub const OP_GET_PRESIGNED_URL: &str = "GetPresignedUrl";
+/// Guest sends a CompleteUploadRequest once all chunks of an upload have been sent, receives the completed Blob.
+/// The provider verifies that all chunks arrived (and the blob checksum, if one was supplied) before committing the blob
+pub const OP_COMPLETE_UPLOAD: &str = "CompleteUpload";
+/// Guest sends an AbortUploadRequest to discard all chunks received for an upload, lack of error indicates success
+pub const OP_ABORT_UPLOAD: &str = "AbortUpload";
 
 /// Represents a single chunk of a segmented file stream
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct FileChunk {
     /// A sequence number that can be used for retry and ordering logic
     pub sequence_no: u64,
+    /// The absolute byte offset of the first byte of this chunk within the blob
+    #[serde(default)]
+    pub offset: u64,
+    /// Identifies the upload or download session this chunk belongs to, so that concurrent transfers of the
+    /// same blob do not collide. The guest chooses this value (e.g. a GUID) when starting an upload and must
+    /// supply it on every subsequent chunk and on the completion or abort request. For downloads, the
+    /// provider copies the `session_id` of the `StreamRequest` onto every chunk it delivers
+    #[serde(default)]
+    pub session_id: Option<String>,
     /// The container in which this file exists
     pub container: String,
     /// The unique ID of the blob
@@ -44,17 +92,68 @@ pub struct FileChunk {
     #[serde(with = "serde_bytes")]
     #[serde(default)]
     pub chunk_bytes: Vec<u8>,
+    /// MIME type of the blob. Only used on the chunk that starts an upload
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Encoding applied to the blob's contents (e.g. `gzip`). Only used on the chunk that starts an upload
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    /// User-defined metadata to store with the blob. Only used on the chunk that starts an upload
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// CRC32C digest of `chunk_bytes`. If supplied, the receiver must verify it before accepting the chunk
+    #[serde(default)]
+    pub checksum: Option<u32>,
+    /// CRC32C digest of the entire blob. If supplied on the chunk that starts an upload, the provider
+    /// verifies it once all chunks have been received
+    #[serde(default)]
+    pub blob_checksum: Option<u32>,
+}
+
+impl FileChunk {
+    /// Sets the `checksum` of this chunk from its current contents
+    pub fn with_checksum(mut self) -> FileChunk {
+        self.checksum = Some(crc32c(&self.chunk_bytes));
+        self
+    }
+
+    /// Verifies the contents of this chunk against its `checksum`. Chunks without a checksum are
+    /// always considered valid
+    pub fn verify_checksum(&self) -> Result<(), ChecksumMismatch> {
+        match self.checksum {
+            Some(expected) => {
+                let actual = crc32c(&self.chunk_bytes);
+                if actual == expected {
+                    Ok(())
+                } else {
+                    Err(ChecksumMismatch {
+                        expected,
+                        actual,
+                        sequence_no: Some(self.sequence_no),
+                    })
+                }
+            }
+            None => Ok(()),
+        }
+    }
 }
 
 impl Sample for FileChunk {
     fn sample() -> Self {
         FileChunk {
             sequence_no: 5,
+            offset: 5120,
+            session_id: Some("upload-b4f1".to_string()),
             container: "container".to_string(),
             id: "blob".to_string(),
             total_bytes: 53400,
             chunk_size: 1024,
             chunk_bytes: vec![1, 2, 3, 4, 5],
+            content_type: Some("application/octet-stream".to_string()),
+            content_encoding: None,
+            metadata: HashMap::new(),
+            checksum: Some(crc32c(&[1, 2, 3, 4, 5])),
+            blob_checksum: None,
         }
     }
 }
@@ -66,6 +165,34 @@ pub struct Container {
     pub id: String,
 }
 
+/// Information about a container within a blob store
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerInfo {
+    /// The unique ID of the container
+    pub id: String,
+    /// Time the container was created (seconds since the epoch), if known to the provider
+    #[serde(default)]
+    pub created: Option<u64>,
+    /// The number of blobs in the container
+    #[serde(default)]
+    pub object_count: u64,
+    /// The total number of bytes of all blobs in the container
+    #[serde(default)]
+    pub total_bytes: u64,
+}
+
+impl Sample for ContainerInfo {
+    fn sample() -> Self {
+        ContainerInfo {
+            id: "container".to_string(),
+            created: Some(1_585_000_000),
+            object_count: 0,
+            total_bytes: 0,
+        }
+    }
+}
+
 /// Used to hold a list of containers
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -85,7 +212,7 @@ impl Sample for ContainerList {
 }
 
 /// Metadata about a blob, not the raw bytes
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Blob {
     /// Unique ID of the blob
@@ -94,6 +221,38 @@ pub struct Blob {
     pub container: String,
     /// Total number of bytes of the blob (file size)
     pub byte_size: u64,
+    /// MIME type of the blob (e.g. `image/png`)
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Encoding applied to the blob's contents (e.g. `gzip`)
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    /// Time the blob was last modified (seconds since the epoch)
+    #[serde(default)]
+    pub last_modified: Option<u64>,
+    /// An opaque identifier for this version of the blob's contents, suitable for use as an HTTP ETag
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// User-defined metadata supplied when the blob was uploaded
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl Sample for Blob {
+    fn sample() -> Self {
+        let mut metadata = HashMap::new();
+        metadata.insert("owner".to_string(), "user175".to_string());
+        Blob {
+            id: "blob".to_string(),
+            container: "container".to_string(),
+            byte_size: 53400,
+            content_type: Some("image/png".to_string()),
+            content_encoding: None,
+            last_modified: Some(1_585_000_000),
+            etag: Some("\"5d41402abc4b2a76\"".to_string()),
+            metadata,
+        }
+    }
 }
 
 /// A wrapper for a list of blobs
@@ -104,9 +263,168 @@ pub struct BlobList {
     pub blobs: Vec<Blob>,
 }
 
-/// A request to begin downloading a stream for a blob
+/// A request to copy a blob to a new location, leaving the source in place
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyObjectRequest {
+    /// The container of the blob to copy
+    pub source_container: String,
+    /// The unique ID of the blob to copy
+    pub source_id: String,
+    /// The container in which to place the copy
+    pub dest_container: String,
+    /// The unique ID of the copy. Any existing blob with this ID is replaced
+    pub dest_id: String,
+}
+
+impl Sample for CopyObjectRequest {
+    fn sample() -> Self {
+        CopyObjectRequest {
+            source_container: "container".to_string(),
+            source_id: "blob".to_string(),
+            dest_container: "archive".to_string(),
+            dest_id: "blob-2020-03".to_string(),
+        }
+    }
+}
+
+/// A request to move (rename) a blob, removing it from its original location
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveObjectRequest {
+    /// The container of the blob to move
+    pub source_container: String,
+    /// The unique ID of the blob to move
+    pub source_id: String,
+    /// The container to move the blob into
+    pub dest_container: String,
+    /// The new unique ID of the blob. Any existing blob with this ID is replaced
+    pub dest_id: String,
+}
+
+/// A request for a URL through which a client can access a blob directly, without the blob's bytes
+/// passing through the actor
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct PresignedUrlRequest {
+    /// The container of the blob
+    pub container: String,
+    /// The unique ID of the blob
+    pub id: String,
+    /// The HTTP method the URL will be used with: `GET` to download the blob or `PUT` to upload it
+    pub method: String,
+    /// Seconds after which the URL will expire
+    #[serde(rename = "expires")]
+    pub expires_s: u64,
+}
+
+impl Sample for PresignedUrlRequest {
+    fn sample() -> Self {
+        PresignedUrlRequest {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            method: "GET".to_string(),
+            expires_s: 900,
+        }
+    }
+}
+
+/// A presigned URL for accessing a blob
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUrlResponse {
+    /// The URL to give to the client
+    pub url: String,
+    /// Headers the client must send with its request for the URL to be accepted
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Time at which the URL expires (seconds since the epoch)
+    pub expires_at: u64,
+}
+
+impl Sample for PresignedUrlResponse {
+    fn sample() -> Self {
+        let mut headers = HashMap::new();
+        headers.insert("x-amz-acl".to_string(), "private".to_string());
+        PresignedUrlResponse {
+            url: "https://container.s3.amazonaws.com/blob?X-Amz-Signature=abc123".to_string(),
+            headers,
+            expires_at: 1_585_000_900,
+        }
+    }
+}
+
+/// A request for a page of the blobs within a container
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListObjectsRequest {
+    /// The container to list
+    pub container: String,
+    /// Only blobs whose IDs begin with this prefix are listed
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Groups blobs into pseudo-directories. Blobs whose IDs contain the delimiter after the prefix are
+    /// not listed individually; instead the portion of their ID up to and including the first such
+    /// delimiter is returned once in `common_prefixes`
+    #[serde(default)]
+    pub delimiter: Option<String>,
+    /// The maximum number of blobs and common prefixes to return. The provider may return fewer, and
+    /// applies its own limit if this is not supplied
+    #[serde(default)]
+    pub max_results: Option<u32>,
+    /// The `next_continuation_token` from the previous page. Not supplied when requesting the first page
+    #[serde(default)]
+    pub continuation_token: Option<String>,
+}
+
+impl Sample for ListObjectsRequest {
+    fn sample() -> Self {
+        ListObjectsRequest {
+            container: "container".to_string(),
+            prefix: Some("images/".to_string()),
+            delimiter: Some("/".to_string()),
+            max_results: Some(100),
+            continuation_token: Some("token-1".to_string()),
+        }
+    }
+}
+
+/// A single page of blobs returned from a list request
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListObjectsResponse {
+    /// The blobs on this page, ordered by ID
+    #[serde(default)]
+    pub blobs: Vec<Blob>,
+    /// Pseudo-directories found when a delimiter was supplied
+    #[serde(default)]
+    pub common_prefixes: Vec<String>,
+    /// Supplied as the `continuation_token` of the next request to fetch the following page. Absent
+    /// on the last page
+    #[serde(default)]
+    pub next_continuation_token: Option<String>,
+}
+
+impl ListObjectsResponse {
+    /// Indicates whether further pages of results are available
+    pub fn is_truncated(&self) -> bool {
+        self.next_continuation_token.is_some()
+    }
+}
+
+impl Sample for ListObjectsResponse {
+    fn sample() -> Self {
+        ListObjectsResponse {
+            blobs: vec![Blob::sample()],
+            common_prefixes: vec!["images/thumbnails/".to_string()],
+            next_continuation_token: Some("token-2".to_string()),
+        }
+    }
+}
+
+/// A request to begin downloading a stream for a blob
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct StreamRequest {
     /// The unique ID of the requested blob
     pub id: String,
@@ -114,6 +432,393 @@ pub struct StreamRequest {
     pub container: String,
     /// The preferred size of chunks to be delivered. Consumers must not assume this is the size of the chunks they will get
     pub chunk_size: u64,
+    /// An optional range of bytes to stream. If not supplied, the entire blob is streamed
+    #[serde(default)]
+    pub range: Option<ByteRange>,
+    /// Identifies this download. It is echoed on every chunk, completion and error message delivered for
+    /// the download, and is used to cancel it
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Enables flow control for this download. The provider delivers at most this many chunks before
+    /// waiting for the guest to grant more credit with `OP_ACK_CHUNKS`. If not supplied, or 0, chunks are
+    /// delivered as fast as the provider can read them
+    #[serde(default)]
+    pub initial_window: Option<u64>,
+    /// Resumes an interrupted stream by skipping all chunks with a lower sequence number. Sequence numbers
+    /// are counted from the start of `range` (or of the blob), so the first chunk delivered begins at
+    /// byte `range.offset + resume_from * chunk_size`
+    #[serde(default)]
+    pub resume_from: Option<u64>,
+}
+
+impl Sample for StreamRequest {
+    fn sample() -> Self {
+        StreamRequest {
+            id: "blob".to_string(),
+            container: "container".to_string(),
+            chunk_size: 1024,
+            range: Some(ByteRange {
+                offset: 4096,
+                length: Some(8192),
+            }),
+            session_id: Some("download-9c2e".to_string()),
+            initial_window: Some(8),
+            resume_from: Some(2),
+        }
+    }
+}
+
+/// A contiguous range of bytes within a blob
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct ByteRange {
+    /// The offset of the first byte in the range
+    pub offset: u64,
+    /// The number of bytes in the range. If not supplied, the range extends to the end of the blob
+    #[serde(default)]
+    pub length: Option<u64>,
+}
+
+impl ByteRange {
+    /// Parses a single range from the value of an HTTP `Range` header (e.g. `bytes=0-499`, `bytes=500-`
+    /// or `bytes=-500`) for a blob of the given size. Returns `None` if the header is malformed, specifies
+    /// multiple ranges, or cannot be satisfied
+    pub fn from_http_header(value: &str, total_bytes: u64) -> Option<ByteRange> {
+        let spec = value.trim().strip_prefix("bytes=")?.trim();
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_at(spec.find('-')?);
+        let end = end[1..].trim();
+        let range = match (start.trim(), end) {
+            ("", "") => return None,
+            ("", suffix) => {
+                let suffix: u64 = suffix.parse().ok()?;
+                let len = suffix.min(total_bytes);
+                ByteRange {
+                    offset: total_bytes - len,
+                    length: Some(len),
+                }
+            }
+            (start, "") => ByteRange {
+                offset: start.parse().ok()?,
+                length: None,
+            },
+            (start, end) => {
+                let start: u64 = start.parse().ok()?;
+                let end: u64 = end.parse::<u64>().ok()?.min(total_bytes.saturating_sub(1));
+                if end < start {
+                    return None;
+                }
+                ByteRange {
+                    offset: start,
+                    length: Some(end - start + 1),
+                }
+            }
+        };
+        range.resolve(total_bytes).map(|_| range)
+    }
+
+    /// Resolves this range against a blob of the given size, returning the inclusive offsets of the
+    /// first and last bytes in the range. Returns `None` if the range does not overlap the blob
+    pub fn resolve(&self, total_bytes: u64) -> Option<(u64, u64)> {
+        if self.offset >= total_bytes || self.length == Some(0) {
+            return None;
+        }
+        let last = match self.length {
+            Some(len) => self.offset.saturating_add(len - 1).min(total_bytes - 1),
+            None => total_bytes - 1,
+        };
+        Some((self.offset, last))
+    }
+
+    /// Produces the value of an HTTP `Content-Range` header for this range of a blob of the given size
+    pub fn content_range(&self, total_bytes: u64) -> String {
+        match self.resolve(total_bytes) {
+            Some((first, last)) => format!("bytes {}-{}/{}", first, last, total_bytes),
+            None => format!("bytes */{}", total_bytes),
+        }
+    }
+}
+
+/// A request to complete an upload after all of its chunks have been sent
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompleteUploadRequest {
+    /// The container of the uploaded blob
+    pub container: String,
+    /// The unique ID of the uploaded blob
+    pub id: String,
+    /// The session ID supplied on the upload's chunks
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// The number of chunks the guest sent. The provider fails the request if any are missing
+    pub total_chunks: u64,
+    /// CRC32C digest of the entire blob, verified by the provider before the blob is committed.
+    /// Overrides any `blob_checksum` supplied when the upload started
+    #[serde(default)]
+    pub blob_checksum: Option<u32>,
+}
+
+impl Sample for CompleteUploadRequest {
+    fn sample() -> Self {
+        CompleteUploadRequest {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            session_id: Some("upload-b4f1".to_string()),
+            total_chunks: 53,
+            blob_checksum: Some(0xE306_9283),
+        }
+    }
+}
+
+/// A request to abandon an in-progress upload and discard any chunks received for it
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbortUploadRequest {
+    /// The container of the blob being uploaded
+    pub container: String,
+    /// The unique ID of the blob being uploaded
+    pub id: String,
+    /// The session ID supplied on the upload's chunks
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Delivered to the guest after the final chunk of a download
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamComplete {
+    /// The container of the downloaded blob
+    pub container: String,
+    /// The unique ID of the downloaded blob
+    pub id: String,
+    /// The session ID of the `StreamRequest` that started the download
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// The number of chunks delivered
+    pub total_chunks: u64,
+    /// The number of bytes delivered
+    pub total_bytes: u64,
+    /// CRC32C digest of all bytes delivered, if the provider computed one
+    #[serde(default)]
+    pub blob_checksum: Option<u32>,
+}
+
+impl Sample for StreamComplete {
+    fn sample() -> Self {
+        StreamComplete {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            session_id: Some("download-9c2e".to_string()),
+            total_chunks: 53,
+            total_bytes: 53400,
+            blob_checksum: Some(0xE306_9283),
+        }
+    }
+}
+
+/// Delivered to the guest when a download terminates because of an error
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamError {
+    /// The container of the blob being downloaded
+    pub container: String,
+    /// The unique ID of the blob being downloaded
+    pub id: String,
+    /// The session ID of the `StreamRequest` that started the download
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// The sequence number of the last chunk delivered before the error, if any. The download can be
+    /// retried from the following chunk using `StreamRequest::resume_from`
+    #[serde(default)]
+    pub last_sequence_no: Option<u64>,
+    /// A description of the error
+    pub error: String,
+}
+
+impl Sample for StreamError {
+    fn sample() -> Self {
+        StreamError {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            session_id: Some("download-9c2e".to_string()),
+            last_sequence_no: Some(12),
+            error: "Storage backend unavailable".to_string(),
+        }
+    }
+}
+
+/// A request to stop an in-flight download
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelDownloadRequest {
+    /// The container of the blob being downloaded
+    pub container: String,
+    /// The unique ID of the blob being downloaded
+    pub id: String,
+    /// The session ID of the `StreamRequest` that started the download
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// Sent by the guest to acknowledge processed chunks of a flow-controlled download and grant the
+/// provider credit to deliver more
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckChunks {
+    /// The container of the blob being downloaded
+    pub container: String,
+    /// The unique ID of the blob being downloaded
+    pub id: String,
+    /// The session ID of the `StreamRequest` that started the download
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// The sequence number of the last chunk the guest has processed
+    pub sequence_no: u64,
+    /// The number of additional chunks the provider may deliver
+    pub credits: u64,
+}
+
+impl Sample for AckChunks {
+    fn sample() -> Self {
+        AckChunks {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            session_id: Some("download-9c2e".to_string()),
+            sequence_no: 3,
+            credits: 4,
+        }
+    }
+}
+
+/// Provider-side flow control for a download. The provider checks `try_send` before delivering each
+/// chunk and applies every `AckChunks` it receives from the guest
+#[derive(Debug, Clone)]
+pub struct SendWindow {
+    container: String,
+    id: String,
+    session_id: Option<String>,
+    window: Option<u64>,
+    credits: Option<u64>,
+    last_ack: Option<u64>,
+}
+
+impl SendWindow {
+    /// Creates the send window for a download, using the request's `initial_window`. A request without
+    /// an initial window, or with an initial window of 0, is not flow controlled
+    pub fn new(request: &StreamRequest) -> SendWindow {
+        let window = request.initial_window.filter(|w| *w > 0);
+        SendWindow {
+            container: request.container.clone(),
+            id: request.id.clone(),
+            session_id: request.session_id.clone(),
+            window,
+            credits: window,
+            last_ack: None,
+        }
+    }
+
+    /// The number of chunks that may be delivered before more credit is needed, or `None` if the
+    /// download is not flow controlled
+    pub fn available(&self) -> Option<u64> {
+        self.credits
+    }
+
+    /// Indicates whether another chunk may be delivered now
+    pub fn can_send(&self) -> bool {
+        self.credits != Some(0)
+    }
+
+    /// Consumes one credit for a chunk about to be delivered. Returns false, consuming nothing, if the
+    /// provider must wait for an acknowledgement first
+    pub fn try_send(&mut self) -> bool {
+        match self.credits {
+            Some(0) => false,
+            Some(ref mut c) => {
+                *c -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Adds the credit granted by an acknowledgement from the guest, never exceeding the initial
+    /// window. Returns false, granting nothing, if the acknowledgement belongs to another download or
+    /// does not advance past the last acknowledged sequence number (e.g. it was delivered twice)
+    pub fn on_ack(&mut self, ack: &AckChunks) -> bool {
+        if ack.container != self.container
+            || ack.id != self.id
+            || ack.session_id != self.session_id
+            || matches!(self.last_ack, Some(last) if ack.sequence_no <= last)
+        {
+            return false;
+        }
+        self.last_ack = Some(ack.sequence_no);
+        if let (Some(c), Some(window)) = (self.credits.as_mut(), self.window) {
+            *c = c.saturating_add(ack.credits).min(window);
+        }
+        true
+    }
+}
+
+/// Guest-side flow control for a download. The guest reports each chunk once it has finished
+/// processing it, and sends any `AckChunks` returned to the provider with `OP_ACK_CHUNKS`. Credit is
+/// replenished in batches of half the window so that the provider is never starved
+#[derive(Debug, Clone)]
+pub struct ReceiveWindow {
+    container: String,
+    id: String,
+    session_id: Option<String>,
+    window: Option<u64>,
+    unacked: u64,
+    last_sequence_no: Option<u64>,
+}
+
+impl ReceiveWindow {
+    /// Creates the receive window for a download started with the given request. As with
+    /// `SendWindow`, an initial window of 0 means the download is not flow controlled
+    pub fn new(request: &StreamRequest) -> ReceiveWindow {
+        ReceiveWindow {
+            container: request.container.clone(),
+            id: request.id.clone(),
+            session_id: request.session_id.clone(),
+            window: request.initial_window.filter(|w| *w > 0),
+            unacked: 0,
+            last_sequence_no: None,
+        }
+    }
+
+    /// Records that a chunk has been processed, returning an acknowledgement to send to the provider
+    /// when enough chunks have accumulated. Never returns an acknowledgement if the download is not
+    /// flow controlled
+    pub fn on_chunk_processed(&mut self, chunk: &FileChunk) -> Option<AckChunks> {
+        let window = self.window?;
+        self.unacked += 1;
+        self.last_sequence_no = Some(chunk.sequence_no);
+        if self.unacked >= (window / 2).max(1) {
+            self.ack()
+        } else {
+            None
+        }
+    }
+
+    /// Produces an acknowledgement for all processed chunks that have not yet been acknowledged,
+    /// regardless of the batching threshold
+    pub fn ack(&mut self) -> Option<AckChunks> {
+        if self.unacked == 0 {
+            return None;
+        }
+        let credits = self.unacked;
+        self.unacked = 0;
+        Some(AckChunks {
+            container: self.container.clone(),
+            id: self.id.clone(),
+            session_id: self.session_id.clone(),
+            sequence_no: self.last_sequence_no.unwrap_or_default(),
+            credits,
+        })
+    }
 }
 
 /// Metadata about an in-progress file transfer
@@ -131,3 +836,944 @@ pub struct Transfer {
     /// Total number of chunks being transferred
     pub total_chunks: u64,
 }
+
+impl Transfer {
+    /// Creates a transfer for a blob of `total_size` bytes split into chunks of `chunk_size` bytes.
+    /// An empty blob is transferred as a single empty chunk. Returns `None` if `chunk_size` is 0 and
+    /// the blob is not empty
+    pub fn new(
+        container: &str,
+        blob_id: &str,
+        chunk_size: u64,
+        total_size: u64,
+    ) -> Option<Transfer> {
+        let total_chunks = if total_size == 0 {
+            1
+        } else if chunk_size == 0 {
+            return None;
+        } else {
+            total_size.div_ceil(chunk_size)
+        };
+        Some(Transfer {
+            blob_id: blob_id.to_string(),
+            container: container.to_string(),
+            chunk_size,
+            total_size,
+            total_chunks,
+        })
+    }
+
+    /// Derives the transfer a chunk belongs to from the metadata it carries. Returns `None` if the
+    /// chunk's `chunk_size` is 0 and its `total_bytes` is not
+    pub fn from_chunk(chunk: &FileChunk) -> Option<Transfer> {
+        Transfer::new(
+            &chunk.container,
+            &chunk.id,
+            chunk.chunk_size,
+            chunk.total_bytes,
+        )
+    }
+}
+
+/// Splits blobs into `FileChunk`s of a fixed size, suitable for `OP_START_UPLOAD`/`OP_UPLOAD_CHUNK`
+/// or `OP_RECEIVE_CHUNK`. Chunks are numbered from 0 unless the chunker is serving a resumed
+/// download, carry their absolute `offset` within the blob, and have their `checksum` set
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    container: String,
+    id: String,
+    chunk_size: u64,
+    chunk_len: usize,
+    session_id: Option<String>,
+    base_offset: u64,
+    first_sequence: u64,
+    blob_size: Option<u64>,
+}
+
+impl Chunker {
+    /// Creates a chunker for the given blob. Returns `None` if `chunk_size` is 0 or too large to
+    /// address on this platform
+    pub fn new(container: &str, id: &str, chunk_size: u64) -> Option<Chunker> {
+        if chunk_size == 0 {
+            return None;
+        }
+        Some(Chunker {
+            container: container.to_string(),
+            id: id.to_string(),
+            chunk_size,
+            chunk_len: usize::try_from(chunk_size).ok()?,
+            session_id: None,
+            base_offset: 0,
+            first_sequence: 0,
+            blob_size: None,
+        })
+    }
+
+    /// Sets the session ID placed on every chunk produced by this chunker
+    pub fn with_session_id(mut self, session_id: &str) -> Chunker {
+        self.session_id = Some(session_id.to_string());
+        self
+    }
+
+    /// Configures the chunker to serve a ranged or resumed download of a blob of `blob_size` bytes,
+    /// as described by a `StreamRequest`. Sequence numbers are counted from the start of the range
+    /// and begin at `resume_from`, offsets are absolute within the blob, and `total_bytes` on every
+    /// chunk is `blob_size`. The bytes given to `chunk_bytes` or `chunk_reader` must begin at
+    /// `start_offset()`
+    pub fn for_range(
+        mut self,
+        range: &ByteRange,
+        resume_from: Option<u64>,
+        blob_size: u64,
+    ) -> Chunker {
+        self.base_offset = range.offset;
+        self.first_sequence = resume_from.unwrap_or_default();
+        self.blob_size = Some(blob_size);
+        self
+    }
+
+    /// The absolute offset within the blob of the first byte of the first chunk produced
+    pub fn start_offset(&self) -> u64 {
+        self.offset_of(self.first_sequence)
+    }
+
+    /// Creates the request that completes an upload of a blob of the given size
+    pub fn complete_request(
+        &self,
+        total_bytes: u64,
+        blob_checksum: Option<u32>,
+    ) -> CompleteUploadRequest {
+        CompleteUploadRequest {
+            container: self.container.clone(),
+            id: self.id.clone(),
+            session_id: self.session_id.clone(),
+            total_chunks: self.transfer(total_bytes).total_chunks,
+            blob_checksum,
+        }
+    }
+
+    /// Describes the transfer of a blob of the given size
+    pub fn transfer(&self, total_bytes: u64) -> Transfer {
+        Transfer::new(&self.container, &self.id, self.chunk_size, total_bytes)
+            .expect("chunker has a non-zero chunk size")
+    }
+
+    /// Splits a byte slice into chunks. Unless the chunker is serving a ranged download, the first
+    /// chunk carries the `blob_checksum` of the entire slice
+    pub fn chunk_bytes(&self, bytes: &[u8]) -> Vec<FileChunk> {
+        let total_bytes = self.blob_size.unwrap_or(bytes.len() as u64);
+        let mut chunks: Vec<FileChunk> = if bytes.is_empty() {
+            vec![self.chunk(self.first_sequence, total_bytes, Vec::new())]
+        } else {
+            bytes
+                .chunks(self.chunk_len)
+                .enumerate()
+                .map(|(i, b)| {
+                    self.chunk(
+                        self.first_sequence.saturating_add(i as u64),
+                        total_bytes,
+                        b.to_vec(),
+                    )
+                })
+                .collect()
+        };
+        if self.blob_size.is_none() {
+            chunks[0].blob_checksum = Some(crc32c(bytes));
+        }
+        chunks
+    }
+
+    /// Produces an iterator of chunks read from `reader`, which must yield exactly `total_bytes` bytes.
+    /// Because the reader is consumed lazily, no `blob_checksum` is set on these chunks
+    pub fn chunk_reader<R: Read>(&self, reader: R, total_bytes: u64) -> ChunkReader<R> {
+        ChunkReader {
+            chunker: self.clone(),
+            reader,
+            total_bytes,
+            bytes_read: 0,
+            next_sequence: self.first_sequence,
+            finished: false,
+        }
+    }
+
+    fn offset_of(&self, sequence_no: u64) -> u64 {
+        self.base_offset
+            .saturating_add(sequence_no.saturating_mul(self.chunk_size))
+    }
+
+    fn chunk(&self, sequence_no: u64, total_bytes: u64, chunk_bytes: Vec<u8>) -> FileChunk {
+        FileChunk {
+            sequence_no,
+            offset: self.offset_of(sequence_no),
+            session_id: self.session_id.clone(),
+            container: self.container.clone(),
+            id: self.id.clone(),
+            total_bytes,
+            chunk_size: self.chunk_size,
+            chunk_bytes,
+            ..Default::default()
+        }
+        .with_checksum()
+    }
+}
+
+/// An iterator over the chunks of a blob read from an `io::Read`, created by `Chunker::chunk_reader`
+pub struct ChunkReader<R: Read> {
+    chunker: Chunker,
+    reader: R,
+    total_bytes: u64,
+    bytes_read: u64,
+    next_sequence: u64,
+    finished: bool,
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = io::Result<FileChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let remaining = self.total_bytes - self.bytes_read;
+        if remaining == 0 && self.next_sequence > self.chunker.first_sequence {
+            self.finished = true;
+            return None;
+        }
+        let len = remaining.min(self.chunker.chunk_size);
+        // len is at most chunk_size, which Chunker::new checked fits in a usize
+        let mut buf = Vec::with_capacity(len as usize);
+        match (&mut self.reader).take(len).read_to_end(&mut buf) {
+            Ok(n) if (n as u64) < len => {
+                self.finished = true;
+                Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Reader ended after {} of {} bytes",
+                        self.bytes_read + n as u64,
+                        self.total_bytes
+                    ),
+                )))
+            }
+            Ok(_) => {
+                let total_bytes = self.chunker.blob_size.unwrap_or(self.total_bytes);
+                let chunk = self.chunker.chunk(self.next_sequence, total_bytes, buf);
+                self.bytes_read += len;
+                self.next_sequence = self.next_sequence.saturating_add(1);
+                Some(Ok(chunk))
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Reasons a `Reassembler` may reject a chunk or fail to produce a blob
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReassemblyError {
+    /// The chunk belongs to a different blob than the one being reassembled
+    WrongBlob { container: String, id: String },
+    /// The chunk belongs to a different upload session than the chunks already accepted
+    WrongSession(Option<String>),
+    /// A chunk with this sequence number has already been accepted
+    Duplicate(u64),
+    /// The sequence number is beyond the last chunk of the transfer
+    OutOfRange(u64),
+    /// The chunk's contents, or the reassembled blob, failed checksum verification
+    Checksum(ChecksumMismatch),
+    /// The transfer cannot be completed until the missing chunks arrive. `first` lists the lowest
+    /// missing sequence numbers (at most `MAX_MISSING_REPORTED` of them) and `total` is the number of
+    /// chunks missing
+    Missing { first: Vec<u64>, total: u64 },
+    /// The reassembled blob is not the size announced by its chunks
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The chunk's length, `offset`, `total_bytes` or `chunk_size` is inconsistent with its position
+    /// in the transfer
+    Misaligned { sequence_no: u64, reason: String },
+}
+
+impl fmt::Display for ReassemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReassemblyError::WrongBlob { container, id } => {
+                write!(f, "Chunk belongs to another blob: {}/{}", container, id)
+            }
+            ReassemblyError::WrongSession(session) => {
+                write!(f, "Chunk belongs to another session: {:?}", session)
+            }
+            ReassemblyError::Duplicate(seq) => write!(f, "Duplicate chunk {}", seq),
+            ReassemblyError::OutOfRange(seq) => write!(f, "Chunk {} is out of range", seq),
+            ReassemblyError::Checksum(e) => e.fmt(f),
+            ReassemblyError::Missing { first, total } => {
+                write!(f, "Missing {} chunks, starting with {:?}", total, first)
+            }
+            ReassemblyError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Reassembled blob is {} bytes, expected {}",
+                actual, expected
+            ),
+            ReassemblyError::Misaligned {
+                sequence_no,
+                reason,
+            } => write!(f, "Chunk {} is misaligned: {}", sequence_no, reason),
+        }
+    }
+}
+
+impl Error for ReassemblyError {}
+
+impl From<ChecksumMismatch> for ReassemblyError {
+    fn from(e: ChecksumMismatch) -> Self {
+        ReassemblyError::Checksum(e)
+    }
+}
+
+/// The maximum number of sequence numbers listed by `ReassemblyError::Missing`
+pub const MAX_MISSING_REPORTED: usize = 100;
+
+/// Collects the chunks of a transfer, which may arrive in any order, and reassembles them into
+/// the complete blob. Only transfers of whole blobs are supported: the chunks of a ranged or resumed
+/// download do not start at sequence number 0 and carry the size of the whole blob, so they can
+/// never be reassembled into a complete transfer
+#[derive(Debug)]
+pub struct Reassembler {
+    transfer: Transfer,
+    chunks: BTreeMap<u64, Vec<u8>>,
+    blob_checksum: Option<u32>,
+    session_id: Option<String>,
+}
+
+impl Reassembler {
+    /// Creates a reassembler for the given transfer
+    pub fn new(transfer: Transfer) -> Reassembler {
+        Reassembler {
+            transfer,
+            chunks: BTreeMap::new(),
+            blob_checksum: None,
+            session_id: None,
+        }
+    }
+
+    /// The transfer being reassembled
+    pub fn transfer(&self) -> &Transfer {
+        &self.transfer
+    }
+
+    /// Accepts a chunk after verifying that it belongs to this transfer and session, is not a duplicate,
+    /// has the length and offset expected for its sequence number, and matches its checksum. An
+    /// `offset` of 0 on any chunk but the first is treated as not supplied, for senders that predate
+    /// the field. The session is established by the first accepted chunk. A rejected chunk does not
+    /// alter the state of the reassembler
+    pub fn accept(&mut self, chunk: FileChunk) -> Result<(), ReassemblyError> {
+        if chunk.container != self.transfer.container || chunk.id != self.transfer.blob_id {
+            return Err(ReassemblyError::WrongBlob {
+                container: chunk.container,
+                id: chunk.id,
+            });
+        }
+        if !self.chunks.is_empty() && chunk.session_id != self.session_id {
+            return Err(ReassemblyError::WrongSession(chunk.session_id));
+        }
+        if chunk.sequence_no >= self.transfer.total_chunks {
+            return Err(ReassemblyError::OutOfRange(chunk.sequence_no));
+        }
+        if self.chunks.contains_key(&chunk.sequence_no) {
+            return Err(ReassemblyError::Duplicate(chunk.sequence_no));
+        }
+        self.check_alignment(&chunk)?;
+        chunk.verify_checksum()?;
+        if chunk.blob_checksum.is_some() {
+            self.blob_checksum = chunk.blob_checksum;
+        }
+        self.session_id = chunk.session_id;
+        self.chunks.insert(chunk.sequence_no, chunk.chunk_bytes);
+        Ok(())
+    }
+
+    fn check_alignment(&self, chunk: &FileChunk) -> Result<(), ReassemblyError> {
+        let misaligned = |reason: String| {
+            Err(ReassemblyError::Misaligned {
+                sequence_no: chunk.sequence_no,
+                reason,
+            })
+        };
+        if chunk.total_bytes != self.transfer.total_size
+            || chunk.chunk_size != self.transfer.chunk_size
+        {
+            return misaligned(format!(
+                "announces {} bytes in chunks of {}, expected {} bytes in chunks of {}",
+                chunk.total_bytes,
+                chunk.chunk_size,
+                self.transfer.total_size,
+                self.transfer.chunk_size
+            ));
+        }
+        // sequence_no is below total_chunks, so this cannot overflow or exceed total_size
+        let offset = chunk.sequence_no * self.transfer.chunk_size;
+        if chunk.offset != offset && chunk.offset != 0 {
+            return misaligned(format!("offset is {}, expected {}", chunk.offset, offset));
+        }
+        let len = (self.transfer.total_size - offset).min(self.transfer.chunk_size);
+        if chunk.chunk_bytes.len() as u64 != len {
+            return misaligned(format!(
+                "contains {} bytes, expected {}",
+                chunk.chunk_bytes.len(),
+                len
+            ));
+        }
+        Ok(())
+    }
+
+    /// Indicates whether every chunk of the transfer has been accepted
+    pub fn is_complete(&self) -> bool {
+        self.chunks.len() as u64 == self.transfer.total_chunks
+    }
+
+    /// The number of chunks that have not yet been accepted
+    pub fn missing_count(&self) -> u64 {
+        self.transfer.total_chunks - self.chunks.len() as u64
+    }
+
+    /// The lowest sequence numbers of chunks that have not yet been accepted, in ascending order and
+    /// at most `limit` of them. Only the gaps between accepted chunks are visited, so this is cheap
+    /// even when the sender announces an enormous number of chunks
+    pub fn missing(&self, limit: usize) -> Vec<u64> {
+        let mut missing = Vec::new();
+        let mut next = 0;
+        let accepted = self
+            .chunks
+            .keys()
+            .copied()
+            .chain(std::iter::once(self.transfer.total_chunks));
+        for seq in accepted {
+            let gap = (next..seq).take(limit - missing.len());
+            missing.extend(gap);
+            if missing.len() == limit {
+                break;
+            }
+            next = seq.saturating_add(1);
+        }
+        missing
+    }
+
+    /// Produces the complete blob, verifying its size and, if one was supplied, its `blob_checksum`
+    pub fn finish(self) -> Result<Vec<u8>, ReassemblyError> {
+        let total = self.missing_count();
+        if total > 0 {
+            return Err(ReassemblyError::Missing {
+                first: self.missing(MAX_MISSING_REPORTED),
+                total,
+            });
+        }
+        let bytes: Vec<u8> = self.chunks.into_values().flatten().collect();
+        if bytes.len() as u64 != self.transfer.total_size {
+            return Err(ReassemblyError::SizeMismatch {
+                expected: self.transfer.total_size,
+                actual: bytes.len() as u64,
+            });
+        }
+        if let Some(expected) = self.blob_checksum {
+            verify_blob_checksum(&bytes, expected)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Indicates that the contents of a chunk or of a completed blob did not match the digest supplied
+/// by the sender
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChecksumMismatch {
+    /// The digest supplied by the sender
+    pub expected: u32,
+    /// The digest of the data actually received
+    pub actual: u32,
+    /// The sequence number of the failing chunk, or `None` if the whole-blob digest failed
+    pub sequence_no: Option<u64>,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sequence_no {
+            Some(seq) => write!(
+                f,
+                "Checksum mismatch on chunk {}: expected {:08x}, got {:08x}",
+                seq, self.expected, self.actual
+            ),
+            None => write!(
+                f,
+                "Blob checksum mismatch: expected {:08x}, got {:08x}",
+                self.expected, self.actual
+            ),
+        }
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// An incremental CRC32C (Castagnoli) digest, for computing the checksum of a blob as its chunks
+/// are written or received
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32c {
+    state: u32,
+}
+
+impl Default for Crc32c {
+    fn default() -> Self {
+        Crc32c::new()
+    }
+}
+
+impl Crc32c {
+    pub fn new() -> Crc32c {
+        Crc32c { state: !0 }
+    }
+
+    /// Adds the given bytes to the digest
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.state =
+                CRC32C_TABLE[((self.state ^ u32::from(*b)) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    /// Returns the digest of all bytes added so far
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+/// Computes the CRC32C (Castagnoli) digest of the given bytes
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut digest = Crc32c::new();
+    digest.update(bytes);
+    digest.finish()
+}
+
+/// Verifies the contents of an entire blob against the digest supplied when its upload started
+pub fn verify_blob_checksum(bytes: &[u8], expected: u32) -> Result<(), ChecksumMismatch> {
+    let actual = crc32c(bytes);
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch {
+            expected,
+            actual,
+            sequence_no: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn crc32c_incremental_matches_one_shot() {
+        let mut digest = Crc32c::new();
+        digest.update(b"1234");
+        digest.update(b"56789");
+        assert_eq!(digest.finish(), crc32c(b"123456789"));
+    }
+
+    #[test]
+    fn chunk_checksum_detects_corruption() {
+        let mut chunk = FileChunk {
+            sequence_no: 3,
+            chunk_bytes: b"hello".to_vec(),
+            ..Default::default()
+        }
+        .with_checksum();
+        assert_eq!(chunk.verify_checksum(), Ok(()));
+        chunk.chunk_bytes[0] = b'j';
+        let err = chunk.verify_checksum().unwrap_err();
+        assert_eq!(err.sequence_no, Some(3));
+        assert_eq!(err.actual, crc32c(b"jello"));
+    }
+
+    #[test]
+    fn blob_checksum_mismatch() {
+        assert_eq!(verify_blob_checksum(b"123456789", 0xE306_9283), Ok(()));
+        let err = verify_blob_checksum(b"123456780", 0xE306_9283).unwrap_err();
+        assert_eq!(err.expected, 0xE306_9283);
+        assert_eq!(err.sequence_no, None);
+    }
+
+    fn chunker(chunk_size: u64) -> Chunker {
+        Chunker::new("container", "blob", chunk_size).unwrap()
+    }
+
+    #[test]
+    fn reassembles_out_of_order_chunks() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut chunks = chunker(10).chunk_bytes(&data);
+        assert_eq!(chunks.len(), 26);
+        chunks.reverse();
+        let mut reassembler = Reassembler::new(Transfer::from_chunk(&chunks[0]).unwrap());
+        for chunk in chunks {
+            reassembler.accept(chunk).unwrap();
+        }
+        assert!(reassembler.is_complete());
+        assert_eq!(reassembler.finish().unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_duplicate_and_out_of_range_chunks() {
+        let chunks = chunker(4).chunk_bytes(b"0123456789");
+        let mut reassembler = Reassembler::new(Transfer::from_chunk(&chunks[0]).unwrap());
+        reassembler
+            .accept(chunker(4).chunk_bytes(b"0123456789").remove(1))
+            .unwrap();
+        assert_eq!(
+            reassembler.accept(chunker(4).chunk_bytes(b"0123456789").remove(1)),
+            Err(ReassemblyError::Duplicate(1))
+        );
+        let mut extra = chunker(4).chunk_bytes(b"0123456789").remove(2);
+        extra.sequence_no = 3;
+        assert_eq!(
+            reassembler.accept(extra),
+            Err(ReassemblyError::OutOfRange(3))
+        );
+        assert_eq!(reassembler.missing_count(), 2);
+    }
+
+    #[test]
+    fn reports_missing_chunks() {
+        let mut chunks = chunker(2).chunk_bytes(b"0123456789");
+        let mut reassembler = Reassembler::new(Transfer::from_chunk(&chunks[0]).unwrap());
+        reassembler.accept(chunks.remove(3)).unwrap();
+        reassembler.accept(chunks.remove(1)).unwrap();
+        assert_eq!(reassembler.missing(10), vec![0, 2, 4]);
+        assert_eq!(reassembler.missing(2), vec![0, 2]);
+        assert_eq!(
+            reassembler.finish(),
+            Err(ReassemblyError::Missing {
+                first: vec![0, 2, 4],
+                total: 3
+            })
+        );
+    }
+
+    #[test]
+    fn missing_report_is_bounded_for_huge_transfers() {
+        let chunk = FileChunk {
+            sequence_no: 1,
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            total_bytes: 1 << 40,
+            chunk_size: 1,
+            chunk_bytes: vec![0],
+            ..Default::default()
+        };
+        let mut reassembler = Reassembler::new(Transfer::from_chunk(&chunk).unwrap());
+        reassembler.accept(chunk).unwrap();
+        match reassembler.finish() {
+            Err(ReassemblyError::Missing { first, total }) => {
+                assert_eq!(first.len(), MAX_MISSING_REPORTED);
+                assert_eq!(&first[..3], &[0, 2, 3]);
+                assert_eq!(total, (1 << 40) - 1);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_chunks() {
+        let mut chunks = chunker(4).chunk_bytes(b"0123456789");
+        let mut reassembler = Reassembler::new(Transfer::from_chunk(&chunks[0]).unwrap());
+        chunks[0].chunk_bytes[0] = b'x';
+        assert!(matches!(
+            reassembler.accept(chunks.remove(0)),
+            Err(ReassemblyError::Checksum(_))
+        ));
+        assert_eq!(reassembler.missing_count(), 3);
+    }
+
+    #[test]
+    fn zero_chunk_size_is_rejected() {
+        assert!(Chunker::new("container", "blob", 0).is_none());
+        assert!(Transfer::new("container", "blob", 0, 10).is_none());
+        assert_eq!(
+            Transfer::new("container", "blob", 0, 0)
+                .unwrap()
+                .total_chunks,
+            1
+        );
+    }
+
+    #[test]
+    fn ranged_chunks_have_absolute_offsets() {
+        let range = ByteRange {
+            offset: 100,
+            length: Some(50),
+        };
+        let chunker = chunker(10).for_range(&range, Some(2), 1000);
+        assert_eq!(chunker.start_offset(), 120);
+        let chunks = chunker.chunk_bytes(&[7; 30]);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].sequence_no, 2);
+        assert_eq!(chunks[0].offset, 120);
+        assert_eq!(chunks[2].offset, 140);
+        assert!(chunks.iter().all(|c| c.total_bytes == 1000));
+        assert_eq!(chunks[0].blob_checksum, None);
+
+        let read: Vec<FileChunk> = chunker
+            .chunk_reader(&[7u8; 30][..], 30)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, chunks);
+    }
+
+    #[test]
+    fn chunk_reader_reports_short_reads() {
+        let mut reader = chunker(4).chunk_reader(&b"012345"[..], 10);
+        assert_eq!(reader.next().unwrap().unwrap().chunk_bytes, b"0123");
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(reader.next().is_none());
+    }
+
+    fn stream_request(initial_window: Option<u64>) -> StreamRequest {
+        StreamRequest {
+            id: "blob".to_string(),
+            container: "container".to_string(),
+            chunk_size: 4,
+            session_id: Some("download-1".to_string()),
+            initial_window,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flow_control_round_trip() {
+        let request = stream_request(Some(4));
+        let mut send = SendWindow::new(&request);
+        let mut receive = ReceiveWindow::new(&request);
+        let chunks = chunker(1)
+            .with_session_id("download-1")
+            .chunk_bytes(b"0123456");
+        let mut delivered = 0;
+        let mut acks = Vec::new();
+        while delivered < chunks.len() {
+            while delivered < chunks.len() && send.try_send() {
+                acks.extend(receive.on_chunk_processed(&chunks[delivered]));
+                delivered += 1;
+            }
+            for ack in acks.drain(..) {
+                assert!(send.on_ack(&ack));
+            }
+        }
+        assert!(send.available().unwrap() <= 4);
+    }
+
+    #[test]
+    fn zero_window_is_not_flow_controlled() {
+        let request = stream_request(Some(0));
+        let mut send = SendWindow::new(&request);
+        assert_eq!(send.available(), None);
+        assert!(send.try_send());
+        let mut receive = ReceiveWindow::new(&request);
+        assert_eq!(receive.on_chunk_processed(&FileChunk::default()), None);
+    }
+
+    #[test]
+    fn duplicate_and_foreign_acks_grant_no_credit() {
+        let request = stream_request(Some(2));
+        let mut send = SendWindow::new(&request);
+        assert!(send.try_send());
+        assert!(send.try_send());
+        assert!(!send.can_send());
+        let ack = AckChunks {
+            container: "container".to_string(),
+            id: "blob".to_string(),
+            session_id: Some("download-1".to_string()),
+            sequence_no: 0,
+            credits: 1,
+        };
+        assert!(send.on_ack(&ack));
+        assert!(!send.on_ack(&ack));
+        assert_eq!(send.available(), Some(1));
+        let foreign = AckChunks {
+            session_id: Some("download-2".to_string()),
+            sequence_no: 1,
+            ..ack
+        };
+        assert!(!send.on_ack(&foreign));
+        let generous = AckChunks {
+            session_id: Some("download-1".to_string()),
+            sequence_no: 1,
+            credits: 100,
+            ..foreign
+        };
+        assert!(send.on_ack(&generous));
+        assert_eq!(send.available(), Some(2));
+    }
+
+    #[test]
+    fn parses_http_ranges() {
+        let range = |offset, length| Some(ByteRange { offset, length });
+        assert_eq!(
+            ByteRange::from_http_header("bytes=0-499", 1000),
+            range(0, Some(500))
+        );
+        assert_eq!(
+            ByteRange::from_http_header(" bytes=500- ", 1000),
+            range(500, None)
+        );
+        assert_eq!(
+            ByteRange::from_http_header("bytes=-300", 1000),
+            range(700, Some(300))
+        );
+        assert_eq!(
+            ByteRange::from_http_header("bytes=-3000", 1000),
+            range(0, Some(1000))
+        );
+        assert_eq!(
+            ByteRange::from_http_header("bytes=900-2000", 1000),
+            range(900, Some(100))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_http_ranges() {
+        assert_eq!(ByteRange::from_http_header("bytes=1000-", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=500-400", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=-0", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=-", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=0-1,5-9", 1000), None);
+        assert_eq!(ByteRange::from_http_header("items=0-1", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=a-b", 1000), None);
+        assert_eq!(ByteRange::from_http_header("bytes=0-10", 0), None);
+    }
+
+    #[test]
+    fn http_range_end_does_not_overflow() {
+        assert_eq!(
+            ByteRange::from_http_header("bytes=0-18446744073709551615", 1000),
+            Some(ByteRange {
+                offset: 0,
+                length: Some(1000)
+            })
+        );
+        assert_eq!(
+            ByteRange::from_http_header("bytes=18446744073709551615-18446744073709551615", 1000),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_ranges_and_content_range() {
+        let range = ByteRange {
+            offset: 10,
+            length: Some(20),
+        };
+        assert_eq!(range.resolve(100), Some((10, 29)));
+        assert_eq!(range.resolve(15), Some((10, 14)));
+        assert_eq!(range.resolve(10), None);
+        assert_eq!(range.content_range(100), "bytes 10-29/100");
+        assert_eq!(range.content_range(5), "bytes */5");
+        let open = ByteRange {
+            offset: 90,
+            length: None,
+        };
+        assert_eq!(open.resolve(100), Some((90, 99)));
+        let huge = ByteRange {
+            offset: 1,
+            length: Some(u64::MAX),
+        };
+        assert_eq!(huge.resolve(100), Some((1, 99)));
+        let empty = ByteRange {
+            offset: 0,
+            length: Some(0),
+        };
+        assert_eq!(empty.resolve(100), None);
+    }
+
+    #[test]
+    fn rejects_misaligned_chunks() {
+        let chunks = chunker(4).chunk_bytes(b"ABCDEFGH");
+        let transfer = || Transfer::from_chunk(&chunks[0]).unwrap();
+
+        let mut short = chunker(4).chunk_bytes(b"AB").remove(0);
+        short.total_bytes = 8;
+        let mut reassembler = Reassembler::new(transfer());
+        assert!(matches!(
+            reassembler.accept(short),
+            Err(ReassemblyError::Misaligned { sequence_no: 0, .. })
+        ));
+
+        let mut long = chunks[1].clone_with_bytes(b"CDEFGH");
+        long.offset = 4;
+        assert!(matches!(
+            reassembler.accept(long),
+            Err(ReassemblyError::Misaligned { sequence_no: 1, .. })
+        ));
+
+        let mut moved = chunks[1].clone_with_bytes(b"EFGH");
+        moved.offset = 2;
+        assert!(matches!(
+            reassembler.accept(moved),
+            Err(ReassemblyError::Misaligned { .. })
+        ));
+
+        let mut resized = chunks[1].clone_with_bytes(b"EFGH");
+        resized.chunk_size = 2;
+        assert!(matches!(
+            reassembler.accept(resized),
+            Err(ReassemblyError::Misaligned { .. })
+        ));
+        assert_eq!(reassembler.missing_count(), 2);
+
+        let mut legacy = chunks[1].clone_with_bytes(b"EFGH");
+        legacy.offset = 0;
+        reassembler.accept(legacy).unwrap();
+        reassembler
+            .accept(chunks[0].clone_with_bytes(b"ABCD"))
+            .unwrap();
+        assert_eq!(reassembler.finish().unwrap(), b"ABCDEFGH");
+    }
+
+    impl FileChunk {
+        fn clone_with_bytes(&self, bytes: &[u8]) -> FileChunk {
+            FileChunk {
+                sequence_no: self.sequence_no,
+                offset: self.offset,
+                container: self.container.clone(),
+                id: self.id.clone(),
+                total_bytes: self.total_bytes,
+                chunk_size: self.chunk_size,
+                chunk_bytes: bytes.to_vec(),
+                ..Default::default()
+            }
+            .with_checksum()
+        }
+    }
+}
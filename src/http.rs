@@ -1,15 +1,65 @@
 //! # HTTP server capability data structures
 //!
-//! This module contains data types for the `wascc:http_server` and `wascc:httpclient` capabilities
+//! This module contains data types for the `wascc:http_server` and `wascc:httpclient` capabilities.
+//! Enable the `http-compat` feature for `TryFrom` conversions to and from the types of the
+//! [http](https://docs.rs/http) crate, and the `compression` feature for gzip and brotli compression
+//! of request and response bodies
 
 use crate::Sample;
-use serde::ser::Serialize;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "http-compat")]
+mod compat;
+#[cfg(feature = "http-compat")]
+pub use compat::ConversionError;
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "compression")]
+pub use compression::{compress, decompress, CompressionError};
+mod builder;
+mod negotiate;
+mod router;
+pub use builder::{status_text, Cookie, InvalidCookie, ResponseBuilder};
+pub use negotiate::{negotiate, negotiate_encoding};
+pub use router::{normalize_path, Handler, Params, Router};
 
 /// Operation invoked on a host to perform an HTTP request
 pub const OP_PERFORM_REQUEST: &str = "PerformRequest";
+/// Operation invoked on a host to perform an HTTP request described by a ClientRequest, receives a ClientResponse
+pub const OP_PERFORM_CLIENT_REQUEST: &str = "PerformClientRequest";
 /// Operation invoked on an actor in response to an inbound HTTP request
 pub const OP_HANDLE_REQUEST: &str = "HandleRequest";
+/// Actor sends a BodyChunkRequest while handling a request whose `body_stream` is set, receives the next
+/// BodyChunk of the request body
+pub const OP_READ_BODY_CHUNK: &str = "ReadBodyChunk";
+/// Actor sends a BodyStreamStart while handling a request to begin streaming the response, lack of error
+/// indicates success
+pub const OP_START_BODY_STREAM: &str = "StartBodyStream";
+/// Actor sends a BodyChunk for each piece of a streamed response body, lack of error indicates success
+pub const OP_BODY_CHUNK: &str = "BodyChunk";
+/// Actor sends a BodyStreamEnd after the last chunk of a streamed response body, lack of error indicates success
+pub const OP_END_BODY_STREAM: &str = "EndBodyStream";
+/// Provider sends a Connection to an actor when a client requests a WebSocket upgrade. Returning an error
+/// rejects the upgrade
+pub const OP_WEBSOCKET_CONNECT: &str = "WebSocketConnect";
+/// Provider sends a WebSocketMessage to an actor for each frame received from a client, and an actor sends
+/// one to the provider to transmit a frame to a client
+pub const OP_WEBSOCKET_MESSAGE: &str = "WebSocketMessage";
+/// Provider sends a ConnectionClose to an actor when a WebSocket client disconnects, and an actor sends one
+/// to the provider to close a WebSocket connection
+pub const OP_WEBSOCKET_CLOSE: &str = "WebSocketClose";
+/// Provider sends a Connection to an actor when a client opens a Server-Sent Events stream (an `Accept:
+/// text/event-stream` request). Returning an error rejects the stream
+pub const OP_SSE_CONNECT: &str = "ServerEventsConnect";
+/// Actor sends a ServerEvent to the provider to emit an event on a Server-Sent Events stream
+pub const OP_SSE_SEND_EVENT: &str = "SendServerEvent";
+/// Provider sends a ConnectionClose to an actor when a Server-Sent Events client disconnects, and an actor
+/// sends one to the provider to end a stream
+pub const OP_SSE_CLOSE: &str = "ServerEventsClose";
 
 /// Describes an HTTP request
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
@@ -21,13 +71,36 @@ pub struct Request {
     pub path: String,
     /// The query string portion of the URL
     pub query_string: String,
-    /// The request headers as a map of key-value pairs
+    /// The request headers as a map of key-value pairs. Repeated headers cannot be represented here;
+    /// prefer `headers`
     #[serde(default)]
     pub header: HashMap<String, String>,
+    /// The request headers, preserving repeated headers and their order
+    #[serde(default)]
+    pub headers: HeaderMap,
     /// The raw bytes of the request body
     #[serde(with = "serde_bytes")]
     #[serde(default)]
     pub body: Vec<u8>,
+    /// Set by the provider when the body is too large to deliver in a single message. `body` is then
+    /// empty and the actor reads the body with `OP_READ_BODY_CHUNK` using this stream ID
+    #[serde(default)]
+    pub body_stream: Option<String>,
+    /// The address of the client (or of the nearest proxy), e.g. `203.0.113.7:51234`
+    #[serde(default)]
+    pub remote_addr: Option<String>,
+    /// The URL scheme with which the request was received (`http` or `https`)
+    #[serde(default)]
+    pub scheme: Option<String>,
+    /// The host (and port, if not the default) to which the request was addressed
+    #[serde(default)]
+    pub host: Option<String>,
+    /// The HTTP version of the request, e.g. `HTTP/1.1`
+    #[serde(default)]
+    pub http_version: Option<String>,
+    /// A unique ID assigned to the request by the provider, for correlating logs
+    #[serde(default)]
+    pub request_id: Option<String>,
 }
 
 impl Sample for Request {
@@ -37,7 +110,164 @@ impl Sample for Request {
             path: "/foo".to_string(),
             query_string: "a=1&b=2".to_string(),
             header: sample_header(),
+            headers: sample_headers(),
             body: b"This is the body of a request".to_vec(),
+            body_stream: None,
+            remote_addr: Some("203.0.113.7:51234".to_string()),
+            scheme: Some("https".to_string()),
+            host: Some("api.example.com".to_string()),
+            http_version: Some("HTTP/1.1".to_string()),
+            request_id: Some("req-6f1c2a".to_string()),
+        }
+    }
+}
+
+impl Request {
+    /// Reconstructs the absolute URL of the request from its scheme, host (falling back to the `Host`
+    /// header), path, and query string. The scheme defaults to `http`. The path is used as-is, only
+    /// gaining a leading slash if it lacks one. Returns `None` if the host is unknown
+    pub fn absolute_url(&self) -> Option<String> {
+        let host = self.host.as_deref().or_else(|| self.get_header("Host"))?;
+        let mut url = format!(
+            "{}://{}{}{}",
+            self.scheme.as_deref().unwrap_or("http"),
+            host,
+            if self.path.starts_with('/') { "" } else { "/" },
+            self.path
+        );
+        if !self.query_string.is_empty() {
+            url.push('?');
+            url.push_str(self.query_string.trim_start_matches('?'));
+        }
+        Some(url)
+    }
+
+    /// Returns the first value of the named header (case-insensitive), falling back to the
+    /// single-valued `header` map if the header is not present in `headers`
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, &self.header, name)
+    }
+
+    /// Returns every value of the named header (case-insensitive), falling back to the
+    /// single-valued `header` map if the header is not present in `headers`
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        header_values(&self.headers, &self.header, name)
+    }
+
+    /// Replaces all values of the named header with the given value
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.insert(name, value);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+
+    /// Adds a value to the named header, keeping any existing values
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+
+    /// Removes all values of the named header
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove(name);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+
+    /// Picks the best of the available media types for the request's `Accept` header (see
+    /// `negotiate`). If the request has no `Accept` header, the first available type is chosen
+    pub fn preferred_content_type<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        match self.get_header("Accept") {
+            Some(accept) => negotiate(accept, available),
+            None => available.first().copied(),
+        }
+    }
+
+    /// Parses the query string into a map of percent-decoded parameter names to all of their values,
+    /// in the order they appear
+    pub fn query_params(&self) -> HashMap<String, Vec<String>> {
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+        for (k, v) in form_urlencoded::parse(self.query_string.trim_start_matches('?').as_bytes()) {
+            params
+                .entry(k.into_owned())
+                .or_default()
+                .push(v.into_owned());
+        }
+        params
+    }
+
+    /// De-serializes the query string into a typed value, e.g. a struct whose fields are the
+    /// query parameters
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T, RequestError> {
+        serde_urlencoded::from_str(self.query_string.trim_start_matches('?'))
+            .map_err(|e| RequestError::InvalidQuery(e.to_string()))
+    }
+
+    /// De-serializes an `application/x-www-form-urlencoded` body into a typed value
+    pub fn form<T: DeserializeOwned>(&self) -> Result<T, RequestError> {
+        serde_urlencoded::from_bytes(&self.body)
+            .map_err(|e| RequestError::InvalidForm(e.to_string()))
+    }
+
+    /// De-serializes a JSON body into a typed value
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, RequestError> {
+        serde_json::from_slice(&self.body).map_err(|e| RequestError::InvalidJson(e.to_string()))
+    }
+
+    /// De-serializes the body according to the request's `Content-Type` header, which must be either
+    /// `application/json` (or a `+json` type) or `application/x-www-form-urlencoded`
+    pub fn parse_body<T: DeserializeOwned>(&self) -> Result<T, RequestError> {
+        let content_type = self.get_header("Content-Type").unwrap_or_default();
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if mime == "application/json" || mime.ends_with("+json") {
+            self.json()
+        } else if mime == "application/x-www-form-urlencoded" {
+            self.form()
+        } else {
+            Err(RequestError::UnsupportedContentType(
+                content_type.to_string(),
+            ))
+        }
+    }
+}
+
+/// Describes why the query string or body of a request could not be parsed. Converts into a
+/// 400/Bad Request `Response` whose body contains the error message
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    /// The query string could not be de-serialized into the requested type
+    InvalidQuery(String),
+    /// The form-encoded body could not be de-serialized into the requested type
+    InvalidForm(String),
+    /// The JSON body could not be de-serialized into the requested type
+    InvalidJson(String),
+    /// The body's content type is not one that can be parsed
+    UnsupportedContentType(String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::InvalidQuery(e) => write!(f, "Invalid query string: {}", e),
+            RequestError::InvalidForm(e) => write!(f, "Invalid form body: {}", e),
+            RequestError::InvalidJson(e) => write!(f, "Invalid JSON body: {}", e),
+            RequestError::UnsupportedContentType(ct) => {
+                write!(f, "Unsupported content type: '{}'", ct)
+            }
+        }
+    }
+}
+
+impl Error for RequestError {}
+
+impl From<RequestError> for Response {
+    fn from(e: RequestError) -> Self {
+        Response {
+            body: e.to_string().into_bytes(),
+            ..Response::bad_request()
         }
     }
 }
@@ -50,6 +280,15 @@ fn sample_header() -> HashMap<String, String> {
     hm
 }
 
+fn sample_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.append("Accept", "application/json");
+    headers.append("Accept", "text/plain");
+    headers.append("Dummy", "value");
+
+    headers
+}
+
 /// Represents an HTTP response
 #[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -59,12 +298,20 @@ pub struct Response {
     /// The string version of the status (e.g. 'OK')
     pub status: String,
     #[serde(default)]
-    /// HTTP response headers as key-value pairs.
+    /// HTTP response headers as key-value pairs. Repeated headers cannot be represented here;
+    /// prefer `headers`
     pub header: HashMap<String, String>,
+    /// HTTP response headers, preserving repeated headers (e.g. `Set-Cookie`) and their order
+    #[serde(default)]
+    pub headers: HeaderMap,
     /// The raw bytes of the body
     #[serde(with = "serde_bytes")]
     #[serde(default)]
     pub body: Vec<u8>,
+    /// Set by the actor when it has already sent this response with `OP_START_BODY_STREAM`. The provider
+    /// ignores the rest of the response
+    #[serde(default)]
+    pub body_stream: Option<String>,
 }
 
 impl Sample for Response {
@@ -73,25 +320,61 @@ impl Sample for Response {
             status_code: 200,
             status: "OK".to_string(),
             header: sample_header(),
+            headers: sample_headers(),
             body: b"This is the body of a response".to_vec(),
+            body_stream: None,
         }
     }
 }
 
 impl Response {
-    /// Creates a response with a given status code and serializes the given payload as JSON
+    /// Creates a response with a given status code and serializes the given payload as JSON, setting the
+    /// `Content-Type` header. If the payload cannot be serialized, a 500/Internal Server Error response
+    /// describing the failure is returned instead
     pub fn json<T>(payload: T, status_code: u32, status: &str) -> Response
     where
         T: Serialize,
     {
+        match Response::try_json(&payload, status_code) {
+            Ok(res) => Response {
+                status: status.to_string(),
+                ..res
+            },
+            Err(e) => Response::internal_server_error(&format!("Failed to serialize JSON: {}", e)),
+        }
+    }
+
+    /// Creates a response with a given status code and serializes the given payload as JSON, setting the
+    /// `Content-Type` header. The status text is derived from the status code
+    pub fn try_json<T>(payload: &T, status_code: u32) -> Result<Response, serde_json::Error>
+    where
+        T: Serialize,
+    {
+        Ok(Response::builder()
+            .status(status_code)
+            .json(payload)?
+            .build())
+    }
+
+    /// Starts building a response, initially 200/OK with no headers or body
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// Creates an empty response with the given status code and its standard status text
+    pub fn with_status(status_code: u32) -> Response {
         Response {
-            body: serde_json::to_string(&payload).unwrap().into_bytes(),
-            header: HashMap::new(),
-            status: status.to_string(),
             status_code,
+            status: status_text(status_code).to_string(),
+            ..Default::default()
         }
     }
 
+    /// Creates a 302/Found response redirecting the client to the given location
+    pub fn redirect(location: &str) -> Response {
+        Response::builder().redirect(302, location).build()
+    }
+
     /// Handy shortcut for creating a 404/Not Found response
     pub fn not_found() -> Response {
         Response {
@@ -128,4 +411,804 @@ impl Response {
             ..Default::default()
         }
     }
+
+    /// Returns the first value of the named header (case-insensitive), falling back to the
+    /// single-valued `header` map if the header is not present in `headers`
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, &self.header, name)
+    }
+
+    /// Returns every value of the named header (case-insensitive), falling back to the
+    /// single-valued `header` map if the header is not present in `headers`
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        header_values(&self.headers, &self.header, name)
+    }
+
+    /// Replaces all values of the named header with the given value
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.insert(name, value);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+
+    /// Adds a value to the named header, keeping any existing values
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+
+    /// Removes all values of the named header
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove(name);
+        sync_legacy_header(&self.headers, &mut self.header, name);
+    }
+}
+
+/// Describes an outbound HTTP request performed by the `wascc:httpclient` capability
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientRequest {
+    /// The HTTP method (e.g. GET, PUT, DELETE)
+    pub method: String,
+    /// The full URL of the request, including scheme, host, path, and query string
+    pub url: String,
+    /// The request headers
+    #[serde(default)]
+    pub headers: HeaderMap,
+    /// The raw bytes of the request body
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+    /// The time (milliseconds) to wait for the complete response before giving up, 0 - provider default
+    #[serde(rename = "timeout")]
+    #[serde(default)]
+    pub timeout_ms: u64,
+    /// Indicates whether redirect responses should be followed. Defaults to true if not supplied
+    #[serde(default = "default_follow_redirects")]
+    pub follow_redirects: bool,
+    /// The maximum number of redirects to follow, 0 - provider default
+    #[serde(default)]
+    pub max_redirects: u32,
+    /// URL of a proxy through which to send the request
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Disables verification of the server's TLS certificate. Only intended for development
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+fn default_follow_redirects() -> bool {
+    true
+}
+
+impl ClientRequest {
+    /// Creates a request with no headers or body that follows redirects and uses the provider's
+    /// default timeout
+    pub fn new(method: &str, url: &str) -> ClientRequest {
+        ClientRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            timeout_ms: 0,
+            follow_redirects: true,
+            max_redirects: 0,
+            proxy: None,
+            insecure: false,
+        }
+    }
+}
+
+impl Sample for ClientRequest {
+    fn sample() -> Self {
+        ClientRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/v1/items?limit=10".to_string(),
+            headers: sample_headers(),
+            body: b"This is the body of a client request".to_vec(),
+            timeout_ms: 5000,
+            follow_redirects: true,
+            max_redirects: 5,
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            insecure: false,
+        }
+    }
+}
+
+/// The response to a ClientRequest
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientResponse {
+    /// The response's numerical status code (e.g. 200)
+    pub status_code: u32,
+    /// The string version of the status (e.g. 'OK')
+    pub status: String,
+    /// HTTP response headers
+    #[serde(default)]
+    pub headers: HeaderMap,
+    /// The raw bytes of the body
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+    /// The URL that produced this response, which differs from the requested URL if redirects were followed
+    pub final_url: String,
+    /// The time (milliseconds) taken to receive the complete response
+    #[serde(rename = "elapsed")]
+    #[serde(default)]
+    pub elapsed_ms: u64,
+}
+
+impl Sample for ClientResponse {
+    fn sample() -> Self {
+        ClientResponse {
+            status_code: 200,
+            status: "OK".to_string(),
+            headers: sample_headers(),
+            body: b"This is the body of a client response".to_vec(),
+            final_url: "https://api.example.com/v2/items?limit=10".to_string(),
+            elapsed_ms: 142,
+        }
+    }
+}
+
+impl From<ClientResponse> for Response {
+    /// Converts a client response into a server response, e.g. for an actor proxying a request
+    fn from(res: ClientResponse) -> Self {
+        Response {
+            status_code: res.status_code,
+            status: res.status,
+            header: res.headers.to_single_valued(),
+            headers: res.headers,
+            body: res.body,
+            body_stream: None,
+        }
+    }
+}
+
+/// A request for the next chunk of a streamed request body
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyChunkRequest {
+    /// The `body_stream` of the request being handled
+    pub stream_id: String,
+    /// The maximum number of bytes to return, 0 - provider default
+    #[serde(default)]
+    pub max_bytes: u64,
+}
+
+/// Begins a streamed response body. The status code and headers are sent to the client immediately,
+/// followed by each BodyChunk as it is received
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyStreamStart {
+    /// An ID chosen by the actor to identify the stream, unique among its in-flight requests
+    pub stream_id: String,
+    /// The response's numerical status code (e.g. 200)
+    pub status_code: u32,
+    /// The string version of the status (e.g. 'OK')
+    pub status: String,
+    /// HTTP response headers
+    #[serde(default)]
+    pub headers: HeaderMap,
+    /// Total number of bytes in the body, if known. If not supplied, the provider uses chunked
+    /// transfer encoding
+    #[serde(default)]
+    pub content_length: Option<u64>,
+}
+
+impl Sample for BodyStreamStart {
+    fn sample() -> Self {
+        BodyStreamStart {
+            stream_id: "stream-7".to_string(),
+            status_code: 200,
+            status: "OK".to_string(),
+            headers: sample_headers(),
+            content_length: Some(1_048_576),
+        }
+    }
+}
+
+/// A single chunk of a streamed request or response body
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyChunk {
+    /// The stream to which this chunk belongs
+    pub stream_id: String,
+    /// A sequence number that can be used for retry and ordering logic, starting at 0
+    pub sequence_no: u64,
+    /// The offset of the first byte of this chunk within the body
+    pub offset: u64,
+    /// The raw bytes contained in this chunk
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub bytes: Vec<u8>,
+    /// Indicates this is the last chunk of a request body. Always false for response chunks, whose
+    /// end is signalled with `OP_END_BODY_STREAM`
+    #[serde(default)]
+    pub last: bool,
+}
+
+impl Sample for BodyChunk {
+    fn sample() -> Self {
+        BodyChunk {
+            stream_id: "stream-7".to_string(),
+            sequence_no: 3,
+            offset: 196_608,
+            bytes: vec![1, 2, 3, 4, 5],
+            last: false,
+        }
+    }
+}
+
+/// Completes a streamed response body
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyStreamEnd {
+    /// The stream being completed
+    pub stream_id: String,
+    /// The number of chunks sent
+    pub total_chunks: u64,
+    /// The number of bytes sent
+    pub total_bytes: u64,
+    /// Set if the actor could not produce the rest of the body. The provider aborts the response
+    /// rather than completing it normally
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+fn get_header<'a>(
+    headers: &'a HeaderMap,
+    legacy: &'a HashMap<String, String>,
+    name: &str,
+) -> Option<&'a str> {
+    headers.get(name).or_else(|| {
+        legacy
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    })
+}
+
+fn header_values<'a>(
+    headers: &'a HeaderMap,
+    legacy: &'a HashMap<String, String>,
+    name: &str,
+) -> Vec<&'a str> {
+    if headers.contains(name) {
+        headers.get_all(name).iter().map(|v| v.as_str()).collect()
+    } else {
+        get_header(headers, legacy, name).into_iter().collect()
+    }
+}
+
+/// Keeps the single-valued header map readable by consumers that predate `HeaderMap`. Multiple
+/// values are combined into one comma-separated value
+fn sync_legacy_header(headers: &HeaderMap, legacy: &mut HashMap<String, String>, name: &str) {
+    legacy.retain(|k, _| !k.eq_ignore_ascii_case(name));
+    if let Some((key, values)) = headers.entry(name) {
+        legacy.insert(key.to_string(), single_value(key, values));
+    }
+}
+
+/// Combines the values of a header into one comma-separated value. `Set-Cookie` values may
+/// themselves contain commas (e.g. in `Expires`) and cannot be combined, so only the last is kept
+fn single_value(name: &str, values: &[String]) -> String {
+    if name.eq_ignore_ascii_case("set-cookie") {
+        values.last().cloned().unwrap_or_default()
+    } else {
+        values.join(", ")
+    }
+}
+
+/// A collection of HTTP headers that allows multiple values per header, preserves the order in
+/// which headers were added, and looks up header names case-insensitively. The case of a header
+/// name is preserved as it was first added. Serializes as a map of header names to lists of values
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HeaderMap {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> HeaderMap {
+        Default::default()
+    }
+
+    /// Returns the first value of the named header
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).first().map(|v| v.as_str())
+    }
+
+    /// Returns all values of the named header, in the order they were added
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.entry(name).map(|(_, v)| v).unwrap_or(&[])
+    }
+
+    /// Indicates whether a header with the given name is present
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Replaces all values of the named header with a single value
+    pub fn insert(&mut self, name: &str, value: &str) {
+        match self.position(name) {
+            Some(i) => self.entries[i].1 = vec![value.to_string()],
+            None => self
+                .entries
+                .push((name.to_string(), vec![value.to_string()])),
+        }
+    }
+
+    /// Adds a value to the named header, after any existing values
+    pub fn append(&mut self, name: &str, value: &str) {
+        match self.position(name) {
+            Some(i) => self.entries[i].1.push(value.to_string()),
+            None => self
+                .entries
+                .push((name.to_string(), vec![value.to_string()])),
+        }
+    }
+
+    /// Removes the named header, returning its values
+    pub fn remove(&mut self, name: &str) -> Option<Vec<String>> {
+        self.position(name).map(|i| self.entries.remove(i).1)
+    }
+
+    /// The number of distinct header names
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Produces a map with one value per header, as used by the single-valued `header` fields.
+    /// Multiple values are combined into one comma-separated value, except for `Set-Cookie`, of
+    /// which only the last value is kept
+    pub fn to_single_valued(&self) -> HashMap<String, String> {
+        self.entries
+            .iter()
+            .map(|(k, v)| (k.clone(), single_value(k, v)))
+            .collect()
+    }
+
+    /// Iterates over each header name and its values, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_slice()))
+    }
+
+    fn entry(&self, name: &str) -> Option<(&str, &[String])> {
+        self.position(name)
+            .map(|i| (self.entries[i].0.as_str(), self.entries[i].1.as_slice()))
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+}
+
+impl From<HashMap<String, String>> for HeaderMap {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut headers = HeaderMap::new();
+        for (k, v) in map {
+            headers.append(&k, &v);
+        }
+        headers
+    }
+}
+
+impl Serialize for HeaderMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for (k, v) in &self.entries {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(HeaderMapVisitor)
+    }
+}
+
+struct HeaderMapVisitor;
+
+impl<'de> Visitor<'de> for HeaderMapVisitor {
+    type Value = HeaderMap;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of header names to lists of values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<HeaderMap, A::Error> {
+        let mut headers = HeaderMap::new();
+        while let Some((k, values)) = access.next_entry::<String, Vec<String>>()? {
+            for v in values {
+                headers.append(&k, &v);
+            }
+        }
+        Ok(headers)
+    }
+}
+
+/// Describes a new WebSocket or Server-Sent Events connection
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connection {
+    /// A provider-assigned ID that identifies the connection in all subsequent messages
+    pub connection_id: String,
+    /// The request that opened the connection
+    pub request: Request,
+}
+
+impl Sample for Connection {
+    fn sample() -> Self {
+        Connection {
+            connection_id: "conn-31".to_string(),
+            request: Request::sample(),
+        }
+    }
+}
+
+/// A single WebSocket message. Text messages carry UTF-8 encoded `data`
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    /// The connection on which the message was received or is to be sent
+    pub connection_id: String,
+    /// Indicates a binary rather than a text message
+    #[serde(default)]
+    pub binary: bool,
+    /// The raw bytes of the message
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub data: Vec<u8>,
+}
+
+impl WebSocketMessage {
+    /// Creates a text message
+    pub fn text(connection_id: &str, text: &str) -> WebSocketMessage {
+        WebSocketMessage {
+            connection_id: connection_id.to_string(),
+            binary: false,
+            data: text.as_bytes().to_vec(),
+        }
+    }
+
+    /// Creates a binary message
+    pub fn binary(connection_id: &str, data: Vec<u8>) -> WebSocketMessage {
+        WebSocketMessage {
+            connection_id: connection_id.to_string(),
+            binary: true,
+            data,
+        }
+    }
+
+    /// Returns the contents of a text message, or `None` for binary messages and invalid UTF-8
+    pub fn as_text(&self) -> Option<&str> {
+        if self.binary {
+            None
+        } else {
+            std::str::from_utf8(&self.data).ok()
+        }
+    }
+}
+
+impl Sample for WebSocketMessage {
+    fn sample() -> Self {
+        WebSocketMessage::text("conn-31", "{\"type\":\"subscribe\"}")
+    }
+}
+
+/// Indicates that a WebSocket or Server-Sent Events connection has been, or should be, closed
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionClose {
+    /// The connection being closed
+    pub connection_id: String,
+    /// The WebSocket close code (e.g. 1000 for a normal closure). Not used for Server-Sent Events
+    #[serde(default)]
+    pub code: u16,
+    /// A human-readable reason for the closure
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl ConnectionClose {
+    /// Creates a normal closure of the connection
+    pub fn normal(connection_id: &str) -> ConnectionClose {
+        ConnectionClose {
+            connection_id: connection_id.to_string(),
+            code: 1000,
+            reason: String::new(),
+        }
+    }
+}
+
+/// An event to emit on a Server-Sent Events stream
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerEvent {
+    /// The stream on which to emit the event
+    pub connection_id: String,
+    /// The event type, dispatched to listeners of that name by the client. If not supplied, the
+    /// client treats the event as a `message`. Line breaks are removed when the event is sent
+    #[serde(default)]
+    pub event: Option<String>,
+    /// The event ID, which the client reports in `Last-Event-ID` when reconnecting. Line breaks and
+    /// NUL characters are removed when the event is sent
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The event payload. Multi-line data (separated by CRLF, CR or LF) is sent as multiple `data`
+    /// fields
+    pub data: String,
+    /// The reconnection delay (milliseconds) the client should use if the stream is interrupted
+    #[serde(rename = "retry")]
+    #[serde(default)]
+    pub retry_ms: Option<u64>,
+}
+
+impl ServerEvent {
+    /// Formats the event in the `text/event-stream` wire format, including the terminating blank line
+    pub fn to_event_stream(&self) -> String {
+        let mut out = String::new();
+        if let Some(ref event) = self.event {
+            let event: String = event.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+            out.push_str(&format!("event: {}\n", event));
+        }
+        if let Some(ref id) = self.id {
+            let id: String = id
+                .chars()
+                .filter(|c| *c != '\r' && *c != '\n' && *c != '\0')
+                .collect();
+            out.push_str(&format!("id: {}\n", id));
+        }
+        if let Some(retry) = self.retry_ms {
+            out.push_str(&format!("retry: {}\n", retry));
+        }
+        for line in self.data.replace("\r\n", "\n").split(['\r', '\n']) {
+            out.push_str(&format!("data: {}\n", line));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+impl Sample for ServerEvent {
+    fn sample() -> Self {
+        ServerEvent {
+            connection_id: "conn-32".to_string(),
+            event: Some("metrics".to_string()),
+            id: Some("1042".to_string()),
+            data: "{\"cpu\":0.42}".to_string(),
+            retry_ms: Some(3000),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header_map_merges_repeated_and_mixed_case_names() {
+        let json = r#"{"Set-Cookie": ["a=1"], "set-cookie": ["b=2", "c=3"], "Accept": ["*/*"]}"#;
+        let headers: HeaderMap = serde_json::from_str(json).unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get_all("SET-COOKIE"), &["a=1", "b=2", "c=3"]);
+        assert_eq!(headers.iter().next().unwrap().0, "Set-Cookie");
+        assert_eq!(headers.get("accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn header_map_round_trips_in_order() {
+        let mut headers = HeaderMap::new();
+        headers.append("X-B", "1");
+        headers.append("X-A", "2");
+        headers.append("x-b", "3");
+        let decoded: HeaderMap = crate::deserialize(&crate::serialize(&headers).unwrap()).unwrap();
+        assert_eq!(decoded, headers);
+        let names: Vec<&str> = decoded.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, vec!["X-B", "X-A"]);
+    }
+
+    #[test]
+    fn header_map_insert_and_remove() {
+        let mut headers = HeaderMap::new();
+        headers.append("Vary", "Accept");
+        headers.append("Vary", "Origin");
+        headers.insert("VARY", "*");
+        assert_eq!(headers.get_all("vary"), &["*"]);
+        assert_eq!(headers.remove("Vary"), Some(vec!["*".to_string()]));
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn multi_value_headers_stay_in_sync_with_legacy_map() {
+        let mut res = Response::ok();
+        res.append_header("Set-Cookie", "a=1");
+        res.append_header("set-cookie", "b=2");
+        assert_eq!(res.header_values("Set-Cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(res.header.get("Set-Cookie").unwrap(), "b=2");
+        res.append_header("Vary", "Accept");
+        res.append_header("vary", "Origin");
+        assert_eq!(res.header.get("Vary").unwrap(), "Accept, Origin");
+        res.remove_header("SET-COOKIE");
+        res.remove_header("Vary");
+        assert!(res.header.is_empty());
+        assert!(res.get_header("Set-Cookie").is_none());
+    }
+
+    #[test]
+    fn single_valued_headers_keep_last_set_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT");
+        headers.append("set-cookie", "b=2");
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+        let single = headers.to_single_valued();
+        assert_eq!(single.get("Set-Cookie").unwrap(), "b=2");
+        assert_eq!(single.get("Accept").unwrap(), "text/html, application/json");
+    }
+
+    #[test]
+    fn server_events_cannot_inject_fields() {
+        let event = ServerEvent {
+            connection_id: "c".to_string(),
+            event: Some("x\ndata: injected".to_string()),
+            id: Some("1\r\nretry: 0\0".to_string()),
+            data: "a\rid: evil\r\nb\nc".to_string(),
+            retry_ms: None,
+        };
+        assert_eq!(
+            event.to_event_stream(),
+            "event: xdata: injected\nid: 1retry: 0\ndata: a\ndata: id: evil\ndata: b\ndata: c\n\n"
+        );
+    }
+
+    #[test]
+    fn server_event_data_keeps_empty_lines() {
+        let mut event = ServerEvent::sample();
+        event.event = None;
+        event.id = None;
+        event.retry_ms = None;
+        event.data = String::new();
+        assert_eq!(event.to_event_stream(), "data: \n\n");
+        event.data = "a\n".to_string();
+        assert_eq!(event.to_event_stream(), "data: a\ndata: \n\n");
+    }
+
+    #[test]
+    fn absolute_url_preserves_path() {
+        let mut req = Request::sample();
+        req.scheme = Some("https".to_string());
+        req.host = Some("example.com".to_string());
+        req.path = "/dir//a/".to_string();
+        req.query_string = "x=1".to_string();
+        assert_eq!(
+            req.absolute_url().unwrap(),
+            "https://example.com/dir//a/?x=1"
+        );
+        req.path = "dir/".to_string();
+        req.query_string = String::new();
+        assert_eq!(req.absolute_url().unwrap(), "https://example.com/dir/");
+        req.host = None;
+        req.remove_header("Host");
+        assert_eq!(req.absolute_url(), None);
+    }
+
+    #[test]
+    fn client_requests_follow_redirects_by_default() {
+        let req: ClientRequest =
+            serde_json::from_str(r#"{"method": "GET", "url": "https://example.com"}"#).unwrap();
+        assert_eq!(req, ClientRequest::new("GET", "https://example.com"));
+        assert!(req.follow_redirects);
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+    }
+
+    fn request_with_body(content_type: &str, body: &str) -> Request {
+        let mut req = Request::sample();
+        req.set_header("Content-Type", content_type);
+        req.body = body.as_bytes().to_vec();
+        req
+    }
+
+    #[test]
+    fn query_params_are_decoded_and_grouped() {
+        let mut req = Request::sample();
+        req.query_string = "?tag=a%20b&tag=c+d&name=caf%C3%A9&empty".to_string();
+        let params = req.query_params();
+        assert_eq!(params["tag"], vec!["a b", "c d"]);
+        assert_eq!(params["name"], vec!["café"]);
+        assert_eq!(params["empty"], vec![""]);
+        assert_eq!(params.len(), 3);
+
+        req.query_string = String::new();
+        assert!(req.query_params().is_empty());
+    }
+
+    #[test]
+    fn query_deserializes_into_types() {
+        let mut req = Request::sample();
+        req.query_string = "?q=hello%20world&page=2".to_string();
+        assert_eq!(
+            req.query::<Search>().unwrap(),
+            Search {
+                q: "hello world".to_string(),
+                page: Some(2)
+            }
+        );
+        req.query_string = "page=x".to_string();
+        assert!(matches!(
+            req.query::<Search>(),
+            Err(RequestError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn bodies_are_parsed_by_content_type() {
+        let expected = Search {
+            q: "a b".to_string(),
+            page: None,
+        };
+        let form = request_with_body("application/x-www-form-urlencoded", "q=a+b");
+        assert_eq!(form.form::<Search>().unwrap(), expected);
+        assert_eq!(form.parse_body::<Search>().unwrap(), expected);
+
+        let json = request_with_body("application/json; charset=utf-8", r#"{"q":"a b"}"#);
+        assert_eq!(json.json::<Search>().unwrap(), expected);
+        assert_eq!(json.parse_body::<Search>().unwrap(), expected);
+
+        let vendor = request_with_body("Application/Vnd.Api+JSON", r#"{"q":"a b"}"#);
+        assert_eq!(vendor.parse_body::<Search>().unwrap(), expected);
+
+        let text = request_with_body("text/plain", "q=a+b");
+        assert_eq!(
+            text.parse_body::<Search>(),
+            Err(RequestError::UnsupportedContentType(
+                "text/plain".to_string()
+            ))
+        );
+        let mut missing = text;
+        missing.remove_header("Content-Type");
+        assert_eq!(
+            missing.parse_body::<Search>(),
+            Err(RequestError::UnsupportedContentType(String::new()))
+        );
+    }
+
+    #[test]
+    fn invalid_bodies_are_reported() {
+        let json = request_with_body("application/json", "{");
+        assert!(matches!(
+            json.parse_body::<Search>(),
+            Err(RequestError::InvalidJson(_))
+        ));
+        let form = request_with_body("application/x-www-form-urlencoded", "page=1");
+        assert!(matches!(
+            form.parse_body::<Search>(),
+            Err(RequestError::InvalidForm(_))
+        ));
+    }
+
+    #[test]
+    fn request_errors_become_bad_requests() {
+        let err = RequestError::UnsupportedContentType("text/plain".to_string());
+        let res: Response = err.clone().into();
+        assert_eq!(res.status_code, 400);
+        assert_eq!(res.status, Response::bad_request().status);
+        assert_eq!(res.body, err.to_string().into_bytes());
+        assert_eq!(
+            String::from_utf8(res.body).unwrap(),
+            "Unsupported content type: 'text/plain'"
+        );
+    }
 }
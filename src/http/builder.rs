@@ -0,0 +1,441 @@
+//! A fluent builder for HTTP responses
+
+use super::Response;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::ser::Serialize;
+use std::error::Error;
+use std::fmt;
+
+/// Bytes that are not RFC 6265 cookie-octets, plus `%` so that encoded values can be decoded
+const COOKIE_VALUE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b',')
+    .add(b';')
+    .add(b'\\')
+    .add(b'%');
+/// Bytes that may not appear in a cookie name, which must be an RFC 2616 token
+const COOKIE_NAME: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b',')
+    .add(b';')
+    .add(b'\\')
+    .add(b'(')
+    .add(b')')
+    .add(b'<')
+    .add(b'>')
+    .add(b'@')
+    .add(b':')
+    .add(b'/')
+    .add(b'[')
+    .add(b']')
+    .add(b'?')
+    .add(b'=')
+    .add(b'{')
+    .add(b'}');
+/// Bytes that would end an attribute or the header itself
+const COOKIE_ATTRIBUTE: &AsciiSet = &CONTROLS.add(b';');
+
+/// Builds a `Response` one piece at a time. Created with `Response::builder()`, which starts with
+/// a 200/OK response with no headers or body
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        ResponseBuilder {
+            response: Response::with_status(200),
+        }
+    }
+}
+
+impl ResponseBuilder {
+    pub fn new() -> ResponseBuilder {
+        Default::default()
+    }
+
+    /// Sets the status code. The status text is set to the standard reason phrase for the code
+    pub fn status(mut self, status_code: u32) -> ResponseBuilder {
+        self.response.status_code = status_code;
+        self.response.status = status_text(status_code).to_string();
+        self
+    }
+
+    /// Overrides the status text derived from the status code
+    pub fn status_text(mut self, status: &str) -> ResponseBuilder {
+        self.response.status = status.to_string();
+        self
+    }
+
+    /// Sets a header, replacing any existing values
+    pub fn header(mut self, name: &str, value: &str) -> ResponseBuilder {
+        self.response.set_header(name, value);
+        self
+    }
+
+    /// Adds a value to a header, keeping any existing values
+    pub fn append_header(mut self, name: &str, value: &str) -> ResponseBuilder {
+        self.response.append_header(name, value);
+        self
+    }
+
+    /// Sets the `Content-Type` header
+    pub fn content_type(self, content_type: &str) -> ResponseBuilder {
+        self.header("Content-Type", content_type)
+    }
+
+    /// Sets the raw bytes of the body
+    pub fn body(mut self, body: Vec<u8>) -> ResponseBuilder {
+        self.response.body = body;
+        self
+    }
+
+    /// Sets a plain text body and its content type
+    pub fn text(self, text: &str) -> ResponseBuilder {
+        self.content_type("text/plain; charset=utf-8")
+            .body(text.as_bytes().to_vec())
+    }
+
+    /// Sets an HTML body and its content type
+    pub fn html(self, html: &str) -> ResponseBuilder {
+        self.content_type("text/html; charset=utf-8")
+            .body(html.as_bytes().to_vec())
+    }
+
+    /// Serializes the payload as the JSON body and sets the content type
+    pub fn json<T: Serialize>(self, payload: &T) -> Result<ResponseBuilder, serde_json::Error> {
+        let body = serde_json::to_vec(payload)?;
+        Ok(self.content_type("application/json").body(body))
+    }
+
+    /// Redirects the client to the given location with the given 3xx status code
+    pub fn redirect(self, status_code: u32, location: &str) -> ResponseBuilder {
+        self.status(status_code).header("Location", location)
+    }
+
+    /// Adds a `Set-Cookie` header for the cookie. See `Cookie` for how unsafe characters are handled
+    pub fn cookie(self, cookie: &Cookie) -> ResponseBuilder {
+        self.append_header("Set-Cookie", &cookie.to_string())
+    }
+
+    /// Sets the `Cache-Control` header
+    pub fn cache_control(self, value: &str) -> ResponseBuilder {
+        self.header("Cache-Control", value)
+    }
+
+    /// Allows any cache to store the response for the given number of seconds
+    pub fn max_age(self, seconds: u64) -> ResponseBuilder {
+        self.cache_control(&format!("public, max-age={}", seconds))
+    }
+
+    /// Prevents clients and intermediaries from caching the response
+    pub fn no_cache(self) -> ResponseBuilder {
+        self.cache_control("no-store, no-cache, must-revalidate")
+    }
+
+    /// Sets the `ETag` header
+    pub fn etag(self, etag: &str) -> ResponseBuilder {
+        self.header("ETag", etag)
+    }
+
+    pub fn build(self) -> Response {
+        self.response
+    }
+}
+
+/// An HTTP cookie, rendered as the value of a `Set-Cookie` header by its `Display` implementation.
+/// The value is percent-encoded wherever it contains bytes other than RFC 6265 cookie-octets (and
+/// `%`), so clients must percent-decode it. Any separators or control characters in the name, and
+/// any control characters or `;` in attributes, are also percent-encoded so that they cannot inject
+/// attributes or headers; use `validate` to reject such cookies instead
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// Number of seconds until the cookie expires. 0 expires the cookie immediately
+    pub max_age: Option<u64>,
+    /// Expiry date in HTTP date format (e.g. `Wed, 21 Oct 2015 07:28:00 GMT`)
+    pub expires: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    /// One of `Strict`, `Lax` or `None`
+    pub same_site: Option<String>,
+}
+
+impl Cookie {
+    pub fn new(name: &str, value: &str) -> Cookie {
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn path(mut self, path: &str) -> Cookie {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Cookie {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn max_age(mut self, seconds: u64) -> Cookie {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn expires(mut self, date: &str) -> Cookie {
+        self.expires = Some(date.to_string());
+        self
+    }
+
+    pub fn secure(mut self) -> Cookie {
+        self.secure = true;
+        self
+    }
+
+    pub fn http_only(mut self) -> Cookie {
+        self.http_only = true;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: &str) -> Cookie {
+        self.same_site = Some(same_site.to_string());
+        self
+    }
+
+    /// Checks that the name is a non-empty token, that no attribute contains control characters or
+    /// `;`, and that `same_site` is one of `Strict`, `Lax` or `None`. The value may contain anything,
+    /// since it is percent-encoded when rendered
+    pub fn validate(&self) -> Result<(), InvalidCookie> {
+        if self.name.is_empty() || !is_unchanged(&self.name, COOKIE_NAME) {
+            return Err(InvalidCookie(format!(
+                "invalid name '{}'",
+                self.name.escape_default()
+            )));
+        }
+        let attributes = [
+            ("Path", &self.path),
+            ("Domain", &self.domain),
+            ("Expires", &self.expires),
+            ("SameSite", &self.same_site),
+        ];
+        for (name, value) in attributes.iter() {
+            if let Some(value) = value {
+                if value.chars().any(|c| c == ';' || c.is_control()) {
+                    return Err(InvalidCookie(format!(
+                        "invalid {} attribute '{}'",
+                        name,
+                        value.escape_default()
+                    )));
+                }
+            }
+        }
+        match self.same_site.as_deref() {
+            None | Some("Strict") | Some("Lax") | Some("None") => Ok(()),
+            Some(other) => Err(InvalidCookie(format!(
+                "invalid SameSite attribute '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Indicates that a cookie's name or attributes contain characters that are not allowed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidCookie(String);
+
+impl fmt::Display for InvalidCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid cookie: {}", self.0)
+    }
+}
+
+impl Error for InvalidCookie {}
+
+fn is_unchanged(s: &str, set: &'static AsciiSet) -> bool {
+    utf8_percent_encode(s, set).to_string() == s
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={}",
+            utf8_percent_encode(&self.name, COOKIE_NAME),
+            utf8_percent_encode(&self.value, COOKIE_VALUE)
+        )?;
+        if let Some(ref path) = self.path {
+            write!(f, "; Path={}", utf8_percent_encode(path, COOKIE_ATTRIBUTE))?;
+        }
+        if let Some(ref domain) = self.domain {
+            write!(
+                f,
+                "; Domain={}",
+                utf8_percent_encode(domain, COOKIE_ATTRIBUTE)
+            )?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(ref expires) = self.expires {
+            write!(
+                f,
+                "; Expires={}",
+                utf8_percent_encode(expires, COOKIE_ATTRIBUTE)
+            )?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if let Some(ref same_site) = self.same_site {
+            write!(
+                f,
+                "; SameSite={}",
+                utf8_percent_encode(same_site, COOKIE_ATTRIBUTE)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the standard reason phrase for an HTTP status code, or an empty string for codes that
+/// have none
+pub fn status_text(status_code: u32) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        418 => "I'm a teapot",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Entity",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_cookie_attributes() {
+        let cookie = Cookie::new("session", "abc123")
+            .path("/")
+            .max_age(3600)
+            .secure()
+            .http_only()
+            .same_site("Lax");
+        assert_eq!(cookie.validate(), Ok(()));
+        assert_eq!(
+            cookie.to_string(),
+            "session=abc123; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn encodes_values_that_would_inject_attributes() {
+        let cookie = Cookie::new("s", "v; Domain=evil.com");
+        assert_eq!(cookie.validate(), Ok(()));
+        assert_eq!(cookie.to_string(), "s=v%3B%20Domain=evil.com");
+        let cookie = Cookie::new("s", "100%\r\nX-Injected: 1");
+        assert_eq!(cookie.to_string(), "s=100%25%0D%0AX-Injected:%201");
+    }
+
+    #[test]
+    fn rejects_unsafe_names_and_attributes() {
+        assert!(Cookie::new("", "v").validate().is_err());
+        assert!(Cookie::new("a=b", "v").validate().is_err());
+        let cookie = Cookie::new("s", "v").path("/; Domain=evil.com");
+        assert!(cookie.validate().is_err());
+        assert_eq!(cookie.to_string(), "s=v; Path=/%3B Domain=evil.com");
+        let cookie = Cookie::new("s", "v").domain("example.com\r\nX-Injected: 1");
+        assert!(cookie.validate().is_err());
+        assert!(!cookie.to_string().contains('\n'));
+        assert!(Cookie::new("s", "v")
+            .same_site("Sometimes")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn builder_sets_status_text_and_headers() {
+        let res = Response::builder()
+            .status(201)
+            .cookie(&Cookie::new("a", "1"))
+            .cookie(&Cookie::new("b", "2"))
+            .text("created")
+            .build();
+        assert_eq!(res.status, "Created");
+        assert_eq!(res.header_values("Set-Cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(
+            res.get_header("Content-Type"),
+            Some("text/plain; charset=utf-8")
+        );
+    }
+}
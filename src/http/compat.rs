@@ -0,0 +1,350 @@
+//! Conversions between this module's types and those of the [http](https://docs.rs/http) crate,
+//! enabled with the `http-compat` feature
+
+use super::{HeaderMap, Request, Response};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// Indicates that a request or response could not be converted because it contained an invalid
+/// method, URI, status code, or header
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError(String);
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP conversion failed: {}", self.0)
+    }
+}
+
+impl Error for ConversionError {}
+
+impl From<::http::Error> for ConversionError {
+    fn from(e: ::http::Error) -> Self {
+        ConversionError(e.to_string())
+    }
+}
+
+impl TryFrom<::http::Request<Vec<u8>>> for Request {
+    type Error = ConversionError;
+
+    /// Converts an `http` crate request. The URI's path and query become `path` and `query_string`,
+    /// and both `headers` and the single-valued `header` map are populated. `scheme` and `host` are
+    /// taken from the URI if it is absolute, with `host` otherwise taken from the `Host` header.
+    /// Header values that are not valid UTF-8 are converted lossily
+    fn try_from(req: ::http::Request<Vec<u8>>) -> Result<Self, Self::Error> {
+        let (parts, body) = req.into_parts();
+        let headers = headers_from_http(&parts.headers);
+        let host = parts
+            .uri
+            .authority()
+            .map(|a| a.as_str())
+            .or_else(|| headers.get("Host"))
+            .map(|h| h.to_string());
+        Ok(Request {
+            method: parts.method.as_str().to_string(),
+            path: parts.uri.path().to_string(),
+            query_string: parts.uri.query().unwrap_or_default().to_string(),
+            header: headers.to_single_valued(),
+            headers,
+            body,
+            body_stream: None,
+            remote_addr: None,
+            scheme: parts.uri.scheme_str().map(|s| s.to_string()),
+            host,
+            http_version: version_from_http(parts.version).map(|v| v.to_string()),
+            request_id: None,
+        })
+    }
+}
+
+impl TryFrom<Request> for ::http::Request<Vec<u8>> {
+    type Error = ConversionError;
+
+    /// Converts to an `http` crate request, validating the method and header names and values. The
+    /// URI is formed from `path` (given a leading slash if it lacks one) and `query_string`. A `path`
+    /// in absolute form (e.g. `http://example.com/foo`) is kept as an absolute URI; it may only
+    /// carry a query of its own if `query_string` is empty. The HTTP version defaults to HTTP/1.1 if
+    /// `http_version` is not supplied
+    fn try_from(req: Request) -> Result<Self, Self::Error> {
+        let method = ::http::Method::from_bytes(req.method.as_bytes())
+            .map_err(|_| ConversionError(format!("invalid method '{}'", req.method)))?;
+        let mut uri = if req.path.starts_with('/') {
+            req.path.clone()
+        } else if req.path.contains("://") {
+            let absolute = req
+                .path
+                .parse::<::http::Uri>()
+                .ok()
+                .filter(|u| u.scheme().is_some() && u.authority().is_some())
+                .ok_or_else(|| ConversionError(format!("invalid absolute URI '{}'", req.path)))?;
+            if absolute.query().is_some() && !req.query_string.is_empty() {
+                return Err(ConversionError(format!(
+                    "absolute URI '{}' has a query and query_string is also set",
+                    req.path
+                )));
+            }
+            req.path.clone()
+        } else {
+            format!("/{}", req.path)
+        };
+        if !req.query_string.is_empty() {
+            uri.push('?');
+            uri.push_str(&req.query_string);
+        }
+        let version = match req.http_version.as_deref() {
+            None | Some("HTTP/1.1") => ::http::Version::HTTP_11,
+            Some("HTTP/0.9") => ::http::Version::HTTP_09,
+            Some("HTTP/1.0") => ::http::Version::HTTP_10,
+            Some("HTTP/2.0") | Some("HTTP/2") => ::http::Version::HTTP_2,
+            Some("HTTP/3.0") | Some("HTTP/3") => ::http::Version::HTTP_3,
+            Some(v) => return Err(ConversionError(format!("invalid HTTP version '{}'", v))),
+        };
+        let mut builder = ::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .version(version);
+        for (name, value) in merged_headers(&req.headers, &req.header) {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(req.body)?)
+    }
+}
+
+impl TryFrom<::http::Response<Vec<u8>>> for Response {
+    type Error = ConversionError;
+
+    /// Converts an `http` crate response. `status` is set to the canonical reason phrase of the
+    /// status code, if it has one. Header values that are not valid UTF-8 are converted lossily
+    fn try_from(res: ::http::Response<Vec<u8>>) -> Result<Self, Self::Error> {
+        let (parts, body) = res.into_parts();
+        let headers = headers_from_http(&parts.headers);
+        Ok(Response {
+            status_code: u32::from(parts.status.as_u16()),
+            status: parts
+                .status
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            header: headers.to_single_valued(),
+            headers,
+            body,
+            body_stream: None,
+        })
+    }
+}
+
+impl TryFrom<Response> for ::http::Response<Vec<u8>> {
+    type Error = ConversionError;
+
+    /// Converts to an `http` crate response, validating the status code (which must be between 100
+    /// and 999) and header names and values
+    fn try_from(res: Response) -> Result<Self, Self::Error> {
+        let status = u16::try_from(res.status_code)
+            .ok()
+            .and_then(|code| ::http::StatusCode::from_u16(code).ok())
+            .ok_or_else(|| ConversionError(format!("invalid status code {}", res.status_code)))?;
+        let mut builder = ::http::Response::builder().status(status);
+        for (name, value) in merged_headers(&res.headers, &res.header) {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(res.body)?)
+    }
+}
+
+/// Converts header values lossily, as values may contain bytes that are not valid UTF-8 (e.g.
+/// Latin-1 text)
+fn headers_from_http(map: &::http::HeaderMap) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in map {
+        headers.append(name.as_str(), &String::from_utf8_lossy(value.as_bytes()));
+    }
+    headers
+}
+
+/// The inverse of the `http_version` mapping used when converting to an `http` crate request
+fn version_from_http(version: ::http::Version) -> Option<&'static str> {
+    match version {
+        ::http::Version::HTTP_09 => Some("HTTP/0.9"),
+        ::http::Version::HTTP_10 => Some("HTTP/1.0"),
+        ::http::Version::HTTP_11 => Some("HTTP/1.1"),
+        ::http::Version::HTTP_2 => Some("HTTP/2.0"),
+        ::http::Version::HTTP_3 => Some("HTTP/3.0"),
+        _ => None,
+    }
+}
+
+/// All values from `headers`, followed by any entries of the single-valued map that are not also
+/// present in `headers`
+fn merged_headers<'a>(
+    headers: &'a HeaderMap,
+    legacy: &'a std::collections::HashMap<String, String>,
+) -> Vec<(&'a str, &'a str)> {
+    headers
+        .iter()
+        .flat_map(|(k, vs)| vs.iter().map(move |v| (k, v.as_str())))
+        .chain(
+            legacy
+                .iter()
+                .filter(|(k, _)| !headers.contains(k))
+                .map(|(k, v)| (k.as_str(), v.as_str())),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Sample;
+
+    fn request(path: &str, query_string: &str) -> Request {
+        let mut req = Request::sample();
+        req.method = "GET".to_string();
+        req.path = path.to_string();
+        req.query_string = query_string.to_string();
+        req.header.clear();
+        req.headers = HeaderMap::new();
+        req.http_version = None;
+        req
+    }
+
+    fn response(status_code: u32) -> Response {
+        let mut res = Response::ok();
+        res.status_code = status_code;
+        res
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let mut req = request("/items/1", "a=1&b=2");
+        req.http_version = Some("HTTP/2.0".to_string());
+        req.append_header("Accept", "text/html");
+        req.append_header("accept", "application/json");
+        req.body = b"body".to_vec();
+
+        let converted = ::http::Request::try_from(req).unwrap();
+        assert_eq!(converted.method(), ::http::Method::GET);
+        assert_eq!(converted.uri(), "/items/1?a=1&b=2");
+        assert_eq!(converted.version(), ::http::Version::HTTP_2);
+        let accept: Vec<&str> = converted
+            .headers()
+            .get_all("accept")
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect();
+        assert_eq!(accept, vec!["text/html", "application/json"]);
+
+        let back = Request::try_from(converted).unwrap();
+        assert_eq!(back.path, "/items/1");
+        assert_eq!(back.query_string, "a=1&b=2");
+        assert_eq!(back.http_version.as_deref(), Some("HTTP/2.0"));
+        assert_eq!(
+            back.header_values("Accept"),
+            vec!["text/html", "application/json"]
+        );
+        assert_eq!(
+            back.header.get("accept").unwrap(),
+            "text/html, application/json"
+        );
+        assert_eq!(back.body, b"body");
+    }
+
+    #[test]
+    fn maps_paths_and_queries_to_uris() {
+        let uri = |path, query| {
+            ::http::Request::try_from(request(path, query))
+                .unwrap()
+                .uri()
+                .to_string()
+        };
+        assert_eq!(uri("items", ""), "/items");
+        assert_eq!(uri("", "q=1"), "/?q=1");
+        assert_eq!(
+            uri("http://example.com/foo", "q=1"),
+            "http://example.com/foo?q=1"
+        );
+        assert_eq!(
+            uri("http://example.com/foo?q=1", ""),
+            "http://example.com/foo?q=1"
+        );
+        assert!(::http::Request::try_from(request("http://example.com/foo?q=1", "r=2")).is_err());
+        assert!(::http::Request::try_from(request("http://", "")).is_err());
+
+        let absolute = ::http::Request::builder()
+            .uri("https://example.com:8443/foo?q=1")
+            .body(Vec::new())
+            .unwrap();
+        let req = Request::try_from(absolute).unwrap();
+        assert_eq!(req.path, "/foo");
+        assert_eq!(req.query_string, "q=1");
+        assert_eq!(req.scheme.as_deref(), Some("https"));
+        assert_eq!(req.host.as_deref(), Some("example.com:8443"));
+    }
+
+    #[test]
+    fn rejects_invalid_methods_and_headers() {
+        let mut req = request("/", "");
+        req.method = "GE T".to_string();
+        assert!(::http::Request::try_from(req).is_err());
+
+        let mut req = request("/", "");
+        req.append_header("Bad Name", "x");
+        assert!(::http::Request::try_from(req).is_err());
+
+        let mut req = request("/", "");
+        req.append_header("X-Injected", "a\r\nb");
+        assert!(::http::Request::try_from(req).is_err());
+
+        let mut req = request("/", "");
+        req.http_version = Some("HTTP/4".to_string());
+        assert!(::http::Request::try_from(req).is_err());
+    }
+
+    #[test]
+    fn decodes_non_utf8_header_values_lossily() {
+        let converted = ::http::Request::builder()
+            .uri("/")
+            .header(
+                "X-Name",
+                ::http::HeaderValue::from_bytes(b"caf\xe9").unwrap(),
+            )
+            .header(
+                "X-Utf8",
+                ::http::HeaderValue::from_bytes("café".as_bytes()).unwrap(),
+            )
+            .body(Vec::new())
+            .unwrap();
+        let req = Request::try_from(converted).unwrap();
+        assert_eq!(req.get_header("X-Name"), Some("caf\u{fffd}"));
+        assert_eq!(req.get_header("X-Utf8"), Some("café"));
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let mut res = response(404);
+        res.append_header("Set-Cookie", "a=1");
+        res.append_header("Set-Cookie", "b=2");
+        res.body = b"missing".to_vec();
+
+        let converted = ::http::Response::try_from(res).unwrap();
+        assert_eq!(converted.status(), ::http::StatusCode::NOT_FOUND);
+        assert_eq!(converted.headers().get_all("set-cookie").iter().count(), 2);
+
+        let back = Response::try_from(converted).unwrap();
+        assert_eq!(back.status_code, 404);
+        assert_eq!(back.status, "Not Found");
+        assert_eq!(back.header_values("Set-Cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(back.header.get("set-cookie").unwrap(), "b=2");
+        assert_eq!(back.body, b"missing");
+    }
+
+    #[test]
+    fn validates_status_codes() {
+        assert!(::http::Response::try_from(response(0)).is_err());
+        assert!(::http::Response::try_from(response(99)).is_err());
+        assert!(::http::Response::try_from(response(1000)).is_err());
+        assert!(::http::Response::try_from(response(u32::MAX)).is_err());
+        assert!(::http::Response::try_from(response(100)).is_ok());
+        assert!(::http::Response::try_from(response(999)).is_ok());
+    }
+}
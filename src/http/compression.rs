@@ -0,0 +1,413 @@
+//! gzip, deflate, and brotli compression of request and response bodies, enabled with the
+//! `compression` feature
+
+use super::{negotiate_encoding, HeaderMap, Request, Response};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Content codings offered by `Response::compress_for`, in order of preference
+const PREFERRED_ENCODINGS: &[&str] = &["br", "gzip", "deflate", "identity"];
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+/// Reasons a body could not be compressed or decompressed
+#[derive(Debug)]
+pub enum CompressionError {
+    /// The content coding is not one of `gzip`, `deflate`, `br`, or `identity`
+    UnsupportedEncoding(String),
+    /// The body is not valid data in the given content coding
+    Io(io::Error),
+    /// The decompressed body would exceed the given maximum number of bytes
+    TooLarge(usize),
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::UnsupportedEncoding(encoding) => {
+                write!(f, "Unsupported content encoding: {}", encoding)
+            }
+            CompressionError::Io(e) => write!(f, "Compression failed: {}", e),
+            CompressionError::TooLarge(max) => {
+                write!(f, "Decompressed body exceeds {} bytes", max)
+            }
+        }
+    }
+}
+
+impl Error for CompressionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CompressionError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CompressionError {
+    fn from(e: io::Error) -> Self {
+        CompressionError::Io(e)
+    }
+}
+
+/// Compresses the bytes with the given content coding (`gzip`, `deflate`, `br`, or `identity`)
+pub fn compress(bytes: &[u8], encoding: &str) -> Result<Vec<u8>, CompressionError> {
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        "deflate" => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        "br" => {
+            let mut out = Vec::new();
+            {
+                let mut encoder = brotli::CompressorWriter::new(
+                    &mut out,
+                    BROTLI_BUFFER_SIZE,
+                    BROTLI_QUALITY,
+                    BROTLI_WINDOW,
+                );
+                encoder.write_all(bytes)?;
+            }
+            Ok(out)
+        }
+        "identity" => Ok(bytes.to_vec()),
+        other => Err(CompressionError::UnsupportedEncoding(other.to_string())),
+    }
+}
+
+/// Decompresses bytes that were compressed with the given content coding (`gzip`, `deflate`, `br`,
+/// or `identity`). Fails with `CompressionError::TooLarge` as soon as the output exceeds `max_size`
+/// bytes, so that a small, highly compressed body cannot exhaust memory
+pub fn decompress(
+    bytes: &[u8],
+    encoding: &str,
+    max_size: usize,
+) -> Result<Vec<u8>, CompressionError> {
+    let limit = (max_size as u64).saturating_add(1);
+    let mut out = Vec::new();
+    match encoding.trim().to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => GzDecoder::new(bytes).take(limit).read_to_end(&mut out)?,
+        "deflate" => ZlibDecoder::new(bytes).take(limit).read_to_end(&mut out)?,
+        "br" => brotli::Decompressor::new(bytes, BROTLI_BUFFER_SIZE)
+            .take(limit)
+            .read_to_end(&mut out)?,
+        "identity" => {
+            out = bytes
+                .iter()
+                .copied()
+                .take(max_size.saturating_add(1))
+                .collect();
+            out.len()
+        }
+        other => return Err(CompressionError::UnsupportedEncoding(other.to_string())),
+    };
+    if out.len() > max_size {
+        return Err(CompressionError::TooLarge(max_size));
+    }
+    Ok(out)
+}
+
+impl Response {
+    /// Compresses the body with the given content coding and sets the `Content-Encoding` header.
+    /// Does nothing if the coding is `identity`, the body is empty or streamed, or the response
+    /// already has a `Content-Encoding`
+    pub fn compress(&mut self, encoding: &str) -> Result<(), CompressionError> {
+        compress_body(
+            &mut self.headers,
+            &mut self.header,
+            &mut self.body,
+            self.body_stream.is_some(),
+            encoding,
+        )
+    }
+
+    /// Compresses the body with the content coding best suited to the request's `Accept-Encoding`
+    /// header, preferring brotli over gzip over deflate, and adds `Accept-Encoding` to the `Vary`
+    /// header. Returns the `Content-Encoding` of the body afterwards (`identity` if it was left
+    /// uncompressed), or `None` if the client accepts none of the codings (in which case a 406/Not
+    /// Acceptable response may be appropriate).
+    ///
+    /// A response that already has a `Content-Encoding` is left untouched. Its existing coding is
+    /// returned if the client accepts it, and `None` otherwise
+    pub fn compress_for(&mut self, req: &Request) -> Result<Option<String>, CompressionError> {
+        self.add_vary("Accept-Encoding");
+        let accept = req.get_header("Accept-Encoding").unwrap_or_default();
+        if let Some(existing) = self.get_header("Content-Encoding") {
+            let accepted = existing
+                .split(',')
+                .map(|e| e.trim())
+                .filter(|e| !e.is_empty())
+                .all(|e| negotiate_encoding(accept, &[e]).is_some());
+            return Ok(if accepted {
+                Some(existing.to_string())
+            } else {
+                None
+            });
+        }
+        let encoding = match negotiate_encoding(accept, PREFERRED_ENCODINGS) {
+            Some(encoding) => encoding,
+            None => return Ok(None),
+        };
+        self.compress(encoding)?;
+        let compressed = self.get_header("Content-Encoding").is_some();
+        Ok(Some(
+            if compressed { encoding } else { "identity" }.to_string(),
+        ))
+    }
+
+    /// Decompresses the body according to its `Content-Encoding` header, undoing each listed coding
+    /// in reverse order, then removes the header. Fails, leaving the response unchanged, if the body
+    /// is not valid or would decompress to more than `max_size` bytes
+    pub fn decompress(&mut self, max_size: usize) -> Result<(), CompressionError> {
+        decompress_body(
+            &mut self.headers,
+            &mut self.header,
+            &mut self.body,
+            max_size,
+        )
+    }
+
+    /// Adds a header name to the `Vary` header unless it is already listed
+    fn add_vary(&mut self, name: &str) {
+        let listed = self
+            .header_values("Vary")
+            .iter()
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim() == "*" || v.trim().eq_ignore_ascii_case(name));
+        if !listed {
+            self.append_header("Vary", name);
+        }
+    }
+}
+
+impl Request {
+    /// Compresses the body with the given content coding and sets the `Content-Encoding` header.
+    /// Does nothing if the coding is `identity`, the body is empty or streamed, or the request
+    /// already has a `Content-Encoding`
+    pub fn compress(&mut self, encoding: &str) -> Result<(), CompressionError> {
+        compress_body(
+            &mut self.headers,
+            &mut self.header,
+            &mut self.body,
+            self.body_stream.is_some(),
+            encoding,
+        )
+    }
+
+    /// Decompresses the body according to its `Content-Encoding` header, undoing each listed coding
+    /// in reverse order, then removes the header. Fails, leaving the request unchanged, if the body
+    /// is not valid or would decompress to more than `max_size` bytes. Because request bodies come
+    /// from clients, `max_size` should be no larger than the actor is prepared to hold in memory
+    pub fn decompress(&mut self, max_size: usize) -> Result<(), CompressionError> {
+        decompress_body(
+            &mut self.headers,
+            &mut self.header,
+            &mut self.body,
+            max_size,
+        )
+    }
+}
+
+fn compress_body(
+    headers: &mut HeaderMap,
+    legacy: &mut HashMap<String, String>,
+    body: &mut Vec<u8>,
+    streamed: bool,
+    encoding: &str,
+) -> Result<(), CompressionError> {
+    let already_encoded = super::get_header(headers, legacy, "Content-Encoding").is_some();
+    if encoding.eq_ignore_ascii_case("identity") || body.is_empty() || streamed || already_encoded {
+        return Ok(());
+    }
+    *body = compress(body, encoding)?;
+    set_header(headers, legacy, "Content-Encoding", Some(encoding));
+    update_content_length(headers, legacy, body.len());
+    Ok(())
+}
+
+fn decompress_body(
+    headers: &mut HeaderMap,
+    legacy: &mut HashMap<String, String>,
+    body: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), CompressionError> {
+    let encodings: Vec<String> = super::header_values(headers, legacy, "Content-Encoding")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect();
+    if encodings.is_empty() {
+        return Ok(());
+    }
+    let mut decoded: Option<Vec<u8>> = None;
+    for encoding in encodings.iter().rev() {
+        let input = decoded.as_deref().unwrap_or(body);
+        decoded = Some(decompress(input, encoding, max_size)?);
+    }
+    *body = decoded.unwrap_or_default();
+    set_header(headers, legacy, "Content-Encoding", None);
+    update_content_length(headers, legacy, body.len());
+    Ok(())
+}
+
+/// Keeps an existing `Content-Length` header in step with the new body length
+fn update_content_length(
+    headers: &mut HeaderMap,
+    legacy: &mut HashMap<String, String>,
+    len: usize,
+) {
+    if super::get_header(headers, legacy, "Content-Length").is_some() {
+        set_header(headers, legacy, "Content-Length", Some(&len.to_string()));
+    }
+}
+
+fn set_header(
+    headers: &mut HeaderMap,
+    legacy: &mut HashMap<String, String>,
+    name: &str,
+    value: Option<&str>,
+) {
+    match value {
+        Some(value) => headers.insert(name, value),
+        None => {
+            headers.remove(name);
+        }
+    }
+    super::sync_legacy_header(headers, legacy, name);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Sample;
+
+    const MAX: usize = 1 << 20;
+
+    fn text() -> Vec<u8> {
+        "hello compression ".repeat(50).into_bytes()
+    }
+
+    fn request(accept_encoding: &str) -> Request {
+        let mut req = Request::sample();
+        req.set_header("Accept-Encoding", accept_encoding);
+        req
+    }
+
+    #[test]
+    fn round_trips_every_coding() {
+        for encoding in &["gzip", "deflate", "br", "identity"] {
+            let compressed = compress(&text(), encoding).unwrap();
+            assert_eq!(decompress(&compressed, encoding, MAX).unwrap(), text());
+        }
+        assert!(matches!(
+            compress(&text(), "zstd"),
+            Err(CompressionError::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn limits_decompressed_size() {
+        let bomb = vec![0u8; 1 << 20];
+        for encoding in &["gzip", "deflate", "br", "identity"] {
+            let compressed = compress(&bomb, encoding).unwrap();
+            assert!(matches!(
+                decompress(&compressed, encoding, 1024),
+                Err(CompressionError::TooLarge(1024))
+            ));
+            assert_eq!(decompress(&compressed, encoding, bomb.len()).unwrap(), bomb);
+        }
+    }
+
+    #[test]
+    fn compresses_for_the_client() {
+        let mut res = Response::builder()
+            .text(std::str::from_utf8(&text()).unwrap())
+            .header("Content-Length", &text().len().to_string())
+            .header("Vary", "Origin")
+            .build();
+        let encoding = res.compress_for(&request("gzip, br;q=0.5")).unwrap();
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert_eq!(res.get_header("Content-Encoding"), Some("gzip"));
+        assert_eq!(res.header_values("Vary"), vec!["Origin", "Accept-Encoding"]);
+        assert_eq!(
+            res.get_header("Content-Length"),
+            Some(res.body.len().to_string().as_str())
+        );
+        res.decompress(MAX).unwrap();
+        assert_eq!(res.body, text());
+        assert_eq!(res.get_header("Content-Encoding"), None);
+        assert_eq!(
+            res.get_header("Content-Length"),
+            Some(text().len().to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn honors_refused_codings() {
+        let mut res = Response::builder().body(text()).build();
+        let encoding = res
+            .compress_for(&request("identity;q=0, br;q=0, *"))
+            .unwrap();
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+
+        let mut res = Response::builder().body(text()).build();
+        let encoding = res.compress_for(&request("*;q=0")).unwrap();
+        assert_eq!(encoding, None);
+        assert_eq!(res.body, text());
+
+        let mut res = Response::builder().body(text()).build();
+        let encoding = res.compress_for(&request("x-unknown")).unwrap();
+        assert_eq!(encoding.as_deref(), Some("identity"));
+        assert_eq!(res.get_header("Content-Encoding"), None);
+    }
+
+    #[test]
+    fn reports_existing_encoding() {
+        let body = compress(&text(), "br").unwrap();
+        let mut res = Response::builder()
+            .header("Content-Encoding", "br")
+            .body(body.clone())
+            .build();
+        let encoding = res.compress_for(&request("br, gzip")).unwrap();
+        assert_eq!(encoding.as_deref(), Some("br"));
+        assert_eq!(res.body, body);
+        let encoding = res.compress_for(&request("gzip")).unwrap();
+        assert_eq!(encoding, None);
+        assert_eq!(res.body, body);
+    }
+
+    #[test]
+    fn failed_decompression_leaves_body_intact() {
+        let mut req = request("gzip");
+        req.body = b"definitely not gzip".to_vec();
+        req.set_header("Content-Encoding", "gzip");
+        assert!(matches!(req.decompress(MAX), Err(CompressionError::Io(_))));
+        assert_eq!(req.body, b"definitely not gzip");
+        assert_eq!(req.get_header("Content-Encoding"), Some("gzip"));
+
+        req.body = compress(&compress(&text(), "gzip").unwrap(), "br").unwrap();
+        req.set_header("Content-Encoding", "gzip, br");
+        let layered = req.body.clone();
+        assert!(matches!(
+            req.decompress(16),
+            Err(CompressionError::TooLarge(16))
+        ));
+        assert_eq!(req.body, layered);
+        req.decompress(MAX).unwrap();
+        assert_eq!(req.body, text());
+    }
+}
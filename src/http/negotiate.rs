@@ -0,0 +1,163 @@
+//! Content negotiation based on the `Accept` and `Accept-Encoding` request headers
+
+/// Picks the best of the available media types (e.g. `application/json`) for the value of an
+/// `Accept` header. Each available type is weighted by the quality of the most specific media range
+/// matching it (`type/subtype`, then `type/*`, then `*/*`); ties are resolved in favor of the type
+/// listed first in `available`. Returns `None` if every available type is unacceptable. An empty
+/// header accepts anything
+pub fn negotiate<'a>(accept: &str, available: &[&'a str]) -> Option<&'a str> {
+    let ranges = parse_weighted(accept);
+    best(available, |candidate| {
+        if ranges.is_empty() {
+            return Some(1.0);
+        }
+        let (ty, subty) = split_media_type(candidate);
+        ranges
+            .iter()
+            .filter_map(|(range, q)| {
+                let (rty, rsubty) = split_media_type(range);
+                let specificity = match (rty.as_str(), rsubty.as_str()) {
+                    ("*", "*") => 0,
+                    (t, "*") if t == ty => 1,
+                    (t, s) if t == ty && s == subty => 2,
+                    _ => return None,
+                };
+                Some((specificity, *q))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+    })
+}
+
+/// Picks the best of the available content codings (e.g. `br`, `gzip`) for the value of an
+/// `Accept-Encoding` header, weighted by quality and then by the order of `available`. The
+/// `identity` coding is acceptable unless explicitly refused. Returns `None` if every available
+/// coding is unacceptable
+pub fn negotiate_encoding<'a>(accept_encoding: &str, available: &[&'a str]) -> Option<&'a str> {
+    let codings = parse_weighted(accept_encoding);
+    best(available, |candidate| {
+        let candidate = candidate.to_ascii_lowercase();
+        codings
+            .iter()
+            .find(|(c, _)| *c == candidate)
+            .or_else(|| codings.iter().find(|(c, _)| c == "*"))
+            .map(|(_, q)| *q)
+            .or(if candidate == "identity" {
+                Some(0.001)
+            } else {
+                None
+            })
+    })
+}
+
+fn best<'a, F>(available: &[&'a str], quality: F) -> Option<&'a str>
+where
+    F: Fn(&str) -> Option<f32>,
+{
+    let mut best: Option<(&'a str, f32)> = None;
+    for candidate in available {
+        if let Some(q) = quality(candidate) {
+            if q > 0.0 && !matches!(best, Some((_, bq)) if bq >= q) {
+                best = Some((candidate, q));
+            }
+        }
+    }
+    best.map(|(c, _)| c)
+}
+
+/// Parses a comma-separated list of values with optional `q` parameters into lowercased values
+/// (without parameters) and their qualities
+fn parse_weighted(header: &str) -> Vec<(String, f32)> {
+    header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let value = parts.next()?.trim().to_ascii_lowercase();
+            if value.is_empty() {
+                return None;
+            }
+            let q = parts
+                .filter_map(|p| {
+                    let (k, v) = p.split_at(p.find('=')?);
+                    if k.trim().eq_ignore_ascii_case("q") {
+                        v[1..].trim().parse::<f32>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            Some((value, q.clamp(0.0, 1.0)))
+        })
+        .collect()
+}
+
+fn split_media_type(media_type: &str) -> (String, String) {
+    let essence = media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match essence.find('/') {
+        Some(i) => (essence[..i].to_string(), essence[i + 1..].to_string()),
+        None => (essence, "*".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prefers_specific_ranges_and_quality() {
+        let available = ["text/plain", "text/html", "application/json"];
+        assert_eq!(
+            negotiate(
+                "text/html;q=0.5, application/*;q=0.8, */*;q=0.1",
+                &available
+            ),
+            Some("application/json")
+        );
+        assert_eq!(
+            negotiate("text/*, text/html;q=0.2", &available),
+            Some("text/plain")
+        );
+        assert_eq!(negotiate("", &available), Some("text/plain"));
+        assert_eq!(negotiate("image/png", &available), None);
+    }
+
+    #[test]
+    fn media_types_with_zero_quality_are_refused() {
+        assert_eq!(
+            negotiate("text/html, application/json;q=0", &["application/json"]),
+            None
+        );
+        assert_eq!(
+            negotiate(
+                "*/*;q=0.2, application/json;q=0",
+                &["application/json", "text/csv"]
+            ),
+            Some("text/csv")
+        );
+        assert_eq!(negotiate("*/*;q=0", &["text/csv"]), None);
+    }
+
+    #[test]
+    fn encodings_with_zero_quality_are_refused() {
+        let available = ["br", "gzip", "identity"];
+        assert_eq!(
+            negotiate_encoding("gzip, br;q=0.9", &available),
+            Some("gzip")
+        );
+        assert_eq!(negotiate_encoding("br;q=0, *", &available), Some("gzip"));
+        assert_eq!(negotiate_encoding("", &available), Some("identity"));
+        assert_eq!(negotiate_encoding("deflate", &available), Some("identity"));
+        assert_eq!(negotiate_encoding("identity;q=0", &available), None);
+        assert_eq!(negotiate_encoding("*;q=0", &available), None);
+        assert_eq!(
+            negotiate_encoding("*;q=0, identity", &available),
+            Some("identity")
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! A lightweight router for actors handling `OP_HANDLE_REQUEST`
+
+use super::{Request, Response};
+use percent_encoding::percent_decode_str;
+
+/// A function that produces the response for a matched route
+pub type Handler = Box<dyn Fn(&Request, &Params) -> Response + Send + Sync>;
+
+/// Dispatches requests to handlers by method and path template. Templates are made up of literal
+/// segments, named parameters (`:id`) which match a single segment, and an optional trailing
+/// wildcard (`*rest`) which matches all remaining segments, e.g. `/users/:id/files/*path`.
+/// Leading, trailing, and repeated slashes are ignored in both templates and request paths.
+///
+/// Request path segments are percent-decoded before they are matched, both against literal
+/// segments and into parameters. Decoded `.` segments are dropped and decoded `..` segments remove
+/// the preceding segment, so parameter and wildcard values never contain dot segments. A request
+/// with a segment that decodes to a value containing `/` or `\` receives a 400/Bad Request response.
+///
+/// Routes are tried in the order they were added. A request whose path matches no route receives
+/// a 404/Not Found response; one whose path matches only routes for other methods receives a
+/// 405/Method Not Allowed response with an `Allow` header
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    method: String,
+    segments: Vec<Segment>,
+    handler: Handler,
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Default::default()
+    }
+
+    /// Adds a route for the given method and path template. Panics if a wildcard is not the last
+    /// segment of the template
+    pub fn route<F>(mut self, method: &str, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        let segments: Vec<Segment> = split_path(template)
+            .map(|s| {
+                if let Some(name) = s.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = s.strip_prefix('*') {
+                    Segment::Wildcard(name.to_string())
+                } else {
+                    Segment::Literal(s.to_string())
+                }
+            })
+            .collect();
+        assert!(
+            segments
+                .iter()
+                .rev()
+                .skip(1)
+                .all(|s| !matches!(s, Segment::Wildcard(_))),
+            "wildcard must be the last segment of route '{}'",
+            template
+        );
+        self.routes.push(Route {
+            method: method.to_ascii_uppercase(),
+            segments,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Adds a route for `GET` requests
+    pub fn get<F>(self, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("GET", template, handler)
+    }
+
+    /// Adds a route for `POST` requests
+    pub fn post<F>(self, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("POST", template, handler)
+    }
+
+    /// Adds a route for `PUT` requests
+    pub fn put<F>(self, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("PUT", template, handler)
+    }
+
+    /// Adds a route for `PATCH` requests
+    pub fn patch<F>(self, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("PATCH", template, handler)
+    }
+
+    /// Adds a route for `DELETE` requests
+    pub fn delete<F>(self, template: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route("DELETE", template, handler)
+    }
+
+    /// Invokes the handler of the first route matching the request, or produces a 404 or 405 response
+    pub fn handle(&self, req: &Request) -> Response {
+        let path = match decode_path(&req.path) {
+            Some(path) => path,
+            None => return Response::bad_request(),
+        };
+        let mut allowed: Vec<&str> = Vec::new();
+        for route in &self.routes {
+            if let Some(params) = route.matches(&path) {
+                if route.method.eq_ignore_ascii_case(&req.method) {
+                    return (route.handler)(req, &params);
+                }
+                if !allowed.contains(&route.method.as_str()) {
+                    allowed.push(&route.method);
+                }
+            }
+        }
+        if allowed.is_empty() {
+            Response::not_found()
+        } else {
+            let mut res = Response::with_status(405);
+            res.set_header("Allow", &allowed.join(", "));
+            res
+        }
+    }
+}
+
+impl Route {
+    fn matches(&self, path: &[String]) -> Option<Params> {
+        let mut params = Params::default();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard(name) => {
+                    let rest = path.get(i..).unwrap_or_default().join("/");
+                    params.values.push((name.clone(), rest));
+                    return Some(params);
+                }
+                Segment::Literal(lit) => {
+                    if path.get(i) != Some(lit) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.values.push((name.clone(), path.get(i)?.clone()));
+                }
+            }
+        }
+        if path.len() == self.segments.len() {
+            Some(params)
+        } else {
+            None
+        }
+    }
+}
+
+/// The parameters extracted from a request path by a matching route. Values are percent-decoded
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Params {
+    values: Vec<(String, String)>,
+}
+
+impl Params {
+    /// Returns the value of the named parameter or wildcard
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterates over the parameter names and values, in the order they appear in the route template
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Normalizes a request path by giving it a single leading slash, removing trailing and repeated
+/// slashes, and resolving `.` and `..` segments (including percent-encoded ones), e.g.
+/// `foo//./baz/../bar/` becomes `/foo/bar`. Other segments are left percent-encoded
+pub fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in split_path(path) {
+        match decode(segment).as_str() {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Splits a request path into percent-decoded segments, dropping `.` segments and resolving `..`
+/// segments. Returns `None` if a segment decodes to a value containing a slash or backslash
+fn decode_path(path: &str) -> Option<Vec<String>> {
+    let mut segments: Vec<String> = Vec::new();
+    for segment in split_path(path) {
+        let segment = decode(segment);
+        match segment.as_str() {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s if s.contains('/') || s.contains('\\') => return None,
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments)
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+fn decode(segment: &str) -> String {
+    percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Sample;
+
+    fn request(method: &str, path: &str) -> Request {
+        let mut req = Request::sample();
+        req.method = method.to_string();
+        req.path = path.to_string();
+        req
+    }
+
+    fn echo(name: &'static str) -> impl Fn(&Request, &Params) -> Response {
+        move |_, params| {
+            let mut res = Response::ok();
+            res.body = params.get(name).unwrap_or_default().as_bytes().to_vec();
+            res
+        }
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .get("/users/:id", echo("id"))
+            .delete("/users/:id", echo("id"))
+            .get("/files/*path", echo("path"))
+            .get("/caf\u{e9}/menu", echo("none"))
+    }
+
+    #[test]
+    fn extracts_decoded_parameters() {
+        let res = router().handle(&request("GET", "users/jane%20doe/"));
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.body, b"jane doe");
+        let res = router().handle(&request("get", "//files/a/b%2Ec"));
+        assert_eq!(res.body, b"a/b.c");
+    }
+
+    #[test]
+    fn unmatched_paths_are_not_found() {
+        assert_eq!(router().handle(&request("GET", "/users")).status_code, 404);
+        assert_eq!(
+            router().handle(&request("GET", "/users/1/x")).status_code,
+            404
+        );
+    }
+
+    #[test]
+    fn other_methods_are_not_allowed() {
+        let res = router().handle(&request("PUT", "/users/1"));
+        assert_eq!(res.status_code, 405);
+        assert_eq!(res.get_header("Allow"), Some("GET, DELETE"));
+    }
+
+    #[test]
+    fn literals_are_matched_after_decoding() {
+        let res = router().handle(&request("GET", "/caf%C3%A9/%6Denu"));
+        assert_eq!(res.status_code, 200);
+    }
+
+    #[test]
+    fn dot_segments_never_reach_parameters() {
+        let res = router().handle(&request("GET", "/files/%2e%2e/%2e%2e/etc/passwd"));
+        assert_eq!(res.status_code, 404);
+        let res = router().handle(&request("GET", "/files/a/./b/%2E%2E/c"));
+        assert_eq!(res.body, b"a/c");
+        let res = router().handle(&request("GET", "/files/..%2F..%2Fetc/passwd"));
+        assert_eq!(res.status_code, 400);
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("foo//./baz/../bar/"), "/foo/bar");
+        assert_eq!(normalize_path("/../%2e%2E/a%20b"), "/a%20b");
+        assert_eq!(normalize_path(""), "/");
+    }
+}
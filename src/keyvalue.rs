@@ -3,23 +3,211 @@
 //! This module contains data types for the `wascc:keyvalue` capability provider
 
 use crate::Sample;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
 
+/// Guest sends an AddRequest, receives an AddResponse. Counters are 32-bit; prefer `OP_ADD_I64`
+/// for counters that may exceed the range of an `i32`
 pub const OP_ADD: &str = "Add";
+/// Guest sends an AddI64Request, receives an AddI64Response
+pub const OP_ADD_I64: &str = "AddI64";
+/// Guest sends an AddF64Request, receives an AddF64Response
+pub const OP_ADD_F64: &str = "AddF64";
+/// Guest sends a GetRequest, receives a GetResponse
 pub const OP_GET: &str = "Get";
+/// Guest sends a SetRequest, receives a SetResponse
 pub const OP_SET: &str = "Set";
+/// Guest sends a DelRequest, receives a DelResponse
 pub const OP_DEL: &str = "Del";
+/// Guest sends a ListClearRequest, receives a ListClearResponse
 pub const OP_CLEAR: &str = "Clear";
+/// Guest sends a ListRangeRequest, receives a ListRangeResponse
 pub const OP_RANGE: &str = "Range";
+/// Guest sends a ListPushRequest to append to the end (right) of a list, receives a ListResponse
 pub const OP_PUSH: &str = "Push";
+/// Guest sends a ListPushRequest to prepend to the start (left) of a list, receives a ListResponse
+pub const OP_PUSH_LEFT: &str = "PushLeft";
+/// Guest sends a ListPopRequest to remove the first (left) item of a list, receives a ListPopResponse
+pub const OP_POP_LEFT: &str = "PopLeft";
+/// Guest sends a ListPopRequest to remove the last (right) item of a list, receives a ListPopResponse
+pub const OP_POP_RIGHT: &str = "PopRight";
+/// Guest sends a ListInsertRequest, receives a ListInsertResponse
+pub const OP_LIST_INSERT: &str = "ListInsert";
+/// Guest sends a ListIndexRequest, receives a ListIndexResponse
+pub const OP_LIST_INDEX: &str = "ListIndex";
+/// Guest sends a ListTrimRequest, receives a ListResponse
+pub const OP_LIST_TRIM: &str = "ListTrim";
+/// Guest sends a ListLengthRequest, receives a ListLengthResponse
+pub const OP_LIST_LENGTH: &str = "ListLength";
+/// Guest sends a ListDelItemRequest, receives a ListResponse
 pub const OP_LIST_DEL: &str = "ListItemDelete";
 
+/// Guest sends a SetAddRequest, receives a SetOperationResponse
 pub const OP_SET_ADD: &str = "SetAdd";
+/// Guest sends a SetRemoveRequest, receives a SetOperationResponse
 pub const OP_SET_REMOVE: &str = "SetRemove";
+/// Guest sends a SetUnionRequest, receives a SetQueryResponse
 pub const OP_SET_UNION: &str = "SetUnion";
+/// Guest sends a SetIntersectionRequest, receives a SetQueryResponse
 pub const OP_SET_INTERSECT: &str = "SetIntersection";
+/// Guest sends a SetQueryRequest, receives a SetQueryResponse
 pub const OP_SET_QUERY: &str = "SetQuery";
+/// Guest sends a KeyExistsQuery, receives a KeyExistsResponse
 pub const OP_KEY_EXISTS: &str = "KeyExists";
 
+/// Guest sends a HashSetRequest, receives a HashOperationResponse
+pub const OP_HASH_SET: &str = "HashSet";
+/// Guest sends a HashGetRequest, receives a HashGetResponse
+pub const OP_HASH_GET: &str = "HashGet";
+/// Guest sends a HashGetAllRequest, receives a HashGetAllResponse
+pub const OP_HASH_GET_ALL: &str = "HashGetAll";
+/// Guest sends a HashDelRequest, receives a HashOperationResponse
+pub const OP_HASH_DEL: &str = "HashDelete";
+/// Guest sends a HashIncrRequest, receives a HashIncrResponse
+pub const OP_HASH_INCR: &str = "HashIncrement";
+
+/// Guest sends a SortedSetAddRequest, receives a SortedSetOperationResponse
+pub const OP_SORTED_SET_ADD: &str = "SortedSetAdd";
+/// Guest sends a SortedSetRangeByScoreRequest, receives a SortedSetRangeResponse
+pub const OP_SORTED_SET_RANGE_BY_SCORE: &str = "SortedSetRangeByScore";
+/// Guest sends a SortedSetRangeByRankRequest, receives a SortedSetRangeResponse
+pub const OP_SORTED_SET_RANGE_BY_RANK: &str = "SortedSetRangeByRank";
+/// Guest sends a SortedSetRemoveRequest, receives a SortedSetOperationResponse
+pub const OP_SORTED_SET_REMOVE: &str = "SortedSetRemove";
+/// Guest sends a SortedSetScoreRequest, receives a SortedSetScoreResponse
+pub const OP_SORTED_SET_SCORE: &str = "SortedSetScore";
+
+/// Guest sends a TransactionRequest, receives a TransactionResponse
+pub const OP_TRANSACTION: &str = "Transaction";
+
+/// Ties a key-value operation to the types exchanged on the wire. Each `OP_*` constant in this
+/// module has a corresponding marker type in `ops` implementing this trait, so that providers can
+/// decode requests and encode responses generically, and the compiler catches any drift between an
+/// operation and its types
+pub trait KeyValueOperation {
+    /// The operation name used on the wire
+    const OP: &'static str;
+    /// The corresponding variant of `Operation`
+    const OPERATION: Operation;
+    /// The type the guest sends
+    type Request: Serialize + DeserializeOwned;
+    /// The type the provider returns
+    type Response: Serialize + DeserializeOwned;
+}
+
+macro_rules! operations {
+    ($($variant:ident => $op:ident: $req:ident -> $res:ident,)*) => {
+        /// Every operation supported by the key-value capability. Each variant corresponds to one
+        /// of the `OP_*` constants in this module and documents the request a guest sends and the
+        /// response it receives, so that all provider implementations honor the same contract
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Operation {
+            $(
+                #[doc = concat!("`", stringify!($req), "` -> `", stringify!($res), "`")]
+                $variant,
+            )*
+        }
+
+        impl Operation {
+            /// All key-value operations, in declaration order
+            pub const ALL: &'static [Operation] = &[$(Operation::$variant,)*];
+
+            /// The operation name (one of the `OP_*` constants) used on the wire
+            pub fn op(&self) -> &'static str {
+                match self {
+                    $(Operation::$variant => $op,)*
+                }
+            }
+
+            /// The name of the type the guest sends for this operation
+            pub fn request_type(&self) -> &'static str {
+                match self {
+                    $(Operation::$variant => stringify!($req),)*
+                }
+            }
+
+            /// The name of the type the provider returns for this operation
+            pub fn response_type(&self) -> &'static str {
+                match self {
+                    $(Operation::$variant => stringify!($res),)*
+                }
+            }
+        }
+
+        /// Marker types implementing `KeyValueOperation`, one per `Operation` variant
+        pub mod ops {
+            use super::*;
+
+            $(
+                #[doc = concat!("The `", stringify!($op), "` operation")]
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub struct $variant;
+
+                impl KeyValueOperation for $variant {
+                    const OP: &'static str = $op;
+                    const OPERATION: Operation = Operation::$variant;
+                    type Request = $req;
+                    type Response = $res;
+                }
+            )*
+        }
+    };
+}
+
+operations! {
+    Add => OP_ADD: AddRequest -> AddResponse,
+    AddI64 => OP_ADD_I64: AddI64Request -> AddI64Response,
+    AddF64 => OP_ADD_F64: AddF64Request -> AddF64Response,
+    Get => OP_GET: GetRequest -> GetResponse,
+    Set => OP_SET: SetRequest -> SetResponse,
+    Del => OP_DEL: DelRequest -> DelResponse,
+    Clear => OP_CLEAR: ListClearRequest -> ListClearResponse,
+    Range => OP_RANGE: ListRangeRequest -> ListRangeResponse,
+    Push => OP_PUSH: ListPushRequest -> ListResponse,
+    PushLeft => OP_PUSH_LEFT: ListPushRequest -> ListResponse,
+    PopLeft => OP_POP_LEFT: ListPopRequest -> ListPopResponse,
+    PopRight => OP_POP_RIGHT: ListPopRequest -> ListPopResponse,
+    ListInsert => OP_LIST_INSERT: ListInsertRequest -> ListInsertResponse,
+    ListIndex => OP_LIST_INDEX: ListIndexRequest -> ListIndexResponse,
+    ListTrim => OP_LIST_TRIM: ListTrimRequest -> ListResponse,
+    ListLength => OP_LIST_LENGTH: ListLengthRequest -> ListLengthResponse,
+    ListDel => OP_LIST_DEL: ListDelItemRequest -> ListResponse,
+    SetAdd => OP_SET_ADD: SetAddRequest -> SetOperationResponse,
+    SetRemove => OP_SET_REMOVE: SetRemoveRequest -> SetOperationResponse,
+    SetUnion => OP_SET_UNION: SetUnionRequest -> SetQueryResponse,
+    SetIntersect => OP_SET_INTERSECT: SetIntersectionRequest -> SetQueryResponse,
+    SetQuery => OP_SET_QUERY: SetQueryRequest -> SetQueryResponse,
+    KeyExists => OP_KEY_EXISTS: KeyExistsQuery -> KeyExistsResponse,
+    HashSet => OP_HASH_SET: HashSetRequest -> HashOperationResponse,
+    HashGet => OP_HASH_GET: HashGetRequest -> HashGetResponse,
+    HashGetAll => OP_HASH_GET_ALL: HashGetAllRequest -> HashGetAllResponse,
+    HashDel => OP_HASH_DEL: HashDelRequest -> HashOperationResponse,
+    HashIncr => OP_HASH_INCR: HashIncrRequest -> HashIncrResponse,
+    SortedSetAdd => OP_SORTED_SET_ADD: SortedSetAddRequest -> SortedSetOperationResponse,
+    SortedSetRangeByScore => OP_SORTED_SET_RANGE_BY_SCORE:
+        SortedSetRangeByScoreRequest -> SortedSetRangeResponse,
+    SortedSetRangeByRank => OP_SORTED_SET_RANGE_BY_RANK:
+        SortedSetRangeByRankRequest -> SortedSetRangeResponse,
+    SortedSetRemove => OP_SORTED_SET_REMOVE: SortedSetRemoveRequest -> SortedSetOperationResponse,
+    SortedSetScore => OP_SORTED_SET_SCORE: SortedSetScoreRequest -> SortedSetScoreResponse,
+    Transaction => OP_TRANSACTION: TransactionRequest -> TransactionResponse,
+}
+
+impl Operation {
+    /// Looks up the operation for an operation name as received by a capability provider
+    pub fn from_op(op: &str) -> Option<Operation> {
+        Operation::ALL.iter().find(|o| o.op() == op).copied()
+    }
+
+    /// Indicates whether this operation may appear as a step within a transaction. Transactions
+    /// cannot be nested
+    pub fn is_transactional(&self) -> bool {
+        *self != Operation::Transaction
+    }
+}
+
 /// A request to get a single value from the K/V store
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -67,10 +255,15 @@ pub struct DelRequest {
     pub key: String,
 }
 
+/// Response to a delete request
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DelResponse {
+    /// The key that was deleted
     pub key: String,
+    /// Indicates whether the key existed before it was deleted
+    #[serde(default)]
+    pub existed: bool,
 }
 
 /// Response to a set request
@@ -95,7 +288,55 @@ pub struct AddResponse {
     pub value: i32,
 }
 
-/// A request to add an item to the end of a list
+/// A request to perform an atomic add operation on a 64-bit integer counter
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddI64Request {
+    pub key: String,
+    pub value: i64,
+}
+
+impl Sample for AddI64Request {
+    fn sample() -> Self {
+        AddI64Request {
+            key: "requests".to_string(),
+            value: 5_000_000_000,
+        }
+    }
+}
+
+/// Result of an atomic add operation on a 64-bit integer counter
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddI64Response {
+    pub value: i64,
+}
+
+/// A request to perform an atomic add operation on a floating-point value
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddF64Request {
+    pub key: String,
+    pub value: f64,
+}
+
+impl Sample for AddF64Request {
+    fn sample() -> Self {
+        AddF64Request {
+            key: "latency_total".to_string(),
+            value: 12.75,
+        }
+    }
+}
+
+/// Result of an atomic add operation on a floating-point value
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddF64Response {
+    pub value: f64,
+}
+
+/// A request to add an item to the end (`OP_PUSH`) or start (`OP_PUSH_LEFT`) of a list
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListPushRequest {
@@ -103,6 +344,97 @@ pub struct ListPushRequest {
     pub value: String,
 }
 
+/// A request to remove and return the first (`OP_POP_LEFT`) or last (`OP_POP_RIGHT`) item of a list
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPopRequest {
+    pub key: String,
+}
+
+/// The item removed from a list by a pop request
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPopResponse {
+    /// The value removed from the list
+    pub value: String,
+    /// Indicates whether an item was removed. This is false if the list was empty or did not exist
+    pub exists: bool,
+}
+
+impl Sample for ListPopResponse {
+    fn sample() -> Self {
+        ListPopResponse {
+            value: "job-42".to_string(),
+            exists: true,
+        }
+    }
+}
+
+/// A request to insert an item immediately before or after the first occurrence of `pivot` in a list
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListInsertRequest {
+    pub key: String,
+    /// The existing list item relative to which the new item is inserted
+    pub pivot: String,
+    pub value: String,
+    /// Insert before the pivot rather than after it
+    #[serde(default)]
+    pub before: bool,
+}
+
+/// Response to a list insert request
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListInsertResponse {
+    /// The number of items in the list after the insert
+    pub new_count: i32,
+    /// Indicates whether the pivot was found. If it was not, nothing was inserted
+    pub pivot_found: bool,
+}
+
+/// A request to read a single item of a list by its zero-based index. Negative indices
+/// count from the end of the list (-1 is the last item)
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListIndexRequest {
+    pub key: String,
+    pub index: i32,
+}
+
+/// The item found at a given list index
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListIndexResponse {
+    /// The value at the requested index
+    pub value: String,
+    /// Indicates whether the index was within the bounds of the list
+    pub exists: bool,
+}
+
+/// A request to trim a list so that it only contains the items between `start` and `stop` (inclusive)
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTrimRequest {
+    pub key: String,
+    pub start: i32,
+    pub stop: i32,
+}
+
+/// A request for the number of items in a list
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListLengthRequest {
+    pub key: String,
+}
+
+/// The number of items in a list. A missing key has a length of 0
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListLengthResponse {
+    pub length: i32,
+}
+
 /// A request to delete all occurences of an item from a list
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -135,7 +467,16 @@ pub struct ListRangeResponse {
     pub values: Vec<String>,
 }
 
-/// Return response from non-range list requests like push and clear
+/// Response to a request to clear a list
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListClearResponse {
+    /// Indicates whether the list existed before it was cleared
+    #[serde(default)]
+    pub existed: bool,
+}
+
+/// Return response from list requests that modify a list, like push, trim, and item delete
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListResponse {
@@ -202,3 +543,431 @@ pub struct SetOperationResponse {
 pub struct KeyExistsQuery {
     pub key: String,
 }
+
+/// Response to a key existence query
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyExistsResponse {
+    /// Indicates whether the key exists
+    pub exists: bool,
+}
+
+impl Sample for KeyExistsResponse {
+    fn sample() -> Self {
+        KeyExistsResponse { exists: true }
+    }
+}
+
+/// Request to set a field within a hash
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashSetRequest {
+    pub key: String,
+    pub field: String,
+    pub value: String,
+}
+
+impl Sample for HashSetRequest {
+    fn sample() -> Self {
+        HashSetRequest {
+            key: "user:175".to_string(),
+            field: "name".to_string(),
+            value: "Alice".to_string(),
+        }
+    }
+}
+
+/// Request to get the value of a single field within a hash
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashGetRequest {
+    pub key: String,
+    pub field: String,
+}
+
+/// The result of a hash field get request
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashGetResponse {
+    /// The value of the field
+    pub value: String,
+    /// Indicates whether the field existed
+    pub exists: bool,
+}
+
+/// Request to get all fields and values within a hash
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashGetAllRequest {
+    pub key: String,
+}
+
+/// All of the fields and values within a hash. A missing key produces an empty map
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashGetAllResponse {
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+impl Sample for HashGetAllResponse {
+    fn sample() -> Self {
+        let mut values = HashMap::new();
+        values.insert("name".to_string(), "Alice".to_string());
+        values.insert("visits".to_string(), "42".to_string());
+        HashGetAllResponse { values }
+    }
+}
+
+/// Request to remove one or more fields from a hash
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashDelRequest {
+    pub key: String,
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+/// Response to a hash set or delete
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashOperationResponse {
+    /// The number of fields that were added (set) or removed (delete)
+    pub count: i32,
+}
+
+/// A request to perform an atomic add on a numeric field within a hash
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashIncrRequest {
+    pub key: String,
+    pub field: String,
+    pub value: i64,
+}
+
+/// Result of an atomic add on a hash field
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashIncrResponse {
+    pub value: i64,
+}
+
+/// Request to add a member to a sorted set, or update the score of an existing member
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetAddRequest {
+    pub key: String,
+    pub value: String,
+    pub score: f64,
+}
+
+impl Sample for SortedSetAddRequest {
+    fn sample() -> Self {
+        SortedSetAddRequest {
+            key: "leaderboard".to_string(),
+            value: "player1".to_string(),
+            score: 1250.5,
+        }
+    }
+}
+
+/// Request to remove a member from a sorted set
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetRemoveRequest {
+    pub key: String,
+    pub value: String,
+}
+
+/// Response to a sorted set add or remove
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetOperationResponse {
+    /// The number of members that were added (add) or removed (remove). Score updates of
+    /// existing members are not counted
+    pub count: i32,
+}
+
+/// Request for the members of a sorted set whose scores fall within `min` and `max` (inclusive)
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetRangeByScoreRequest {
+    pub key: String,
+    pub min: f64,
+    pub max: f64,
+    /// Return members from highest to lowest score instead of lowest to highest
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+/// Request for the members of a sorted set between the `start` and `stop` ranks (inclusive,
+/// zero-based). Negative ranks count from the end of the set, as with list ranges
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetRangeByRankRequest {
+    pub key: String,
+    pub start: i32,
+    pub stop: i32,
+    /// Rank members from highest to lowest score instead of lowest to highest
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+/// A sorted set member along with its score
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoredValue {
+    pub value: String,
+    pub score: f64,
+}
+
+/// Members returned from a sorted set range request, in rank order
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetRangeResponse {
+    #[serde(default)]
+    pub values: Vec<ScoredValue>,
+}
+
+impl Sample for SortedSetRangeResponse {
+    fn sample() -> Self {
+        SortedSetRangeResponse {
+            values: vec![
+                ScoredValue {
+                    value: "player1".to_string(),
+                    score: 1250.5,
+                },
+                ScoredValue {
+                    value: "player2".to_string(),
+                    score: 980.0,
+                },
+            ],
+        }
+    }
+}
+
+/// Request for the score of a single sorted set member
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetScoreRequest {
+    pub key: String,
+    pub value: String,
+}
+
+/// The score of a sorted set member
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortedSetScoreResponse {
+    pub score: f64,
+    /// Indicates whether the member existed
+    pub exists: bool,
+}
+
+/// A single step within a transaction, consisting of the name of a key-value operation and
+/// that operation's serialized request
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStep {
+    /// The operation to perform (one of the `OP_*` constants other than `OP_TRANSACTION`)
+    pub op: String,
+    /// The serialized request for the operation, e.g. a `SetRequest` for `OP_SET`
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub request: Vec<u8>,
+}
+
+impl TransactionStep {
+    /// Creates a transaction step by serializing the request for the given operation
+    pub fn new<T: Serialize>(op: &str, request: T) -> Result<TransactionStep, Box<dyn Error>> {
+        match Operation::from_op(op) {
+            Some(o) if o.is_transactional() => Ok(TransactionStep {
+                op: op.to_string(),
+                request: crate::serialize(request)?,
+            }),
+            _ => Err(format!("Operation '{}' cannot be used in a transaction", op).into()),
+        }
+    }
+}
+
+/// A request to atomically execute an ordered list of key-value operations. If any of the
+/// keys in `watch` are modified by another client before the transaction executes, the
+/// transaction is aborted and none of its steps are applied
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionRequest {
+    /// The operations to perform, in order
+    #[serde(default)]
+    pub steps: Vec<TransactionStep>,
+    /// Keys that must remain unmodified for the transaction to be applied
+    #[serde(default)]
+    pub watch: Vec<String>,
+}
+
+impl TransactionRequest {
+    /// Creates an empty transaction
+    pub fn new() -> TransactionRequest {
+        Default::default()
+    }
+
+    /// Appends an operation to the transaction
+    pub fn add_step<T: Serialize>(&mut self, op: &str, request: T) -> Result<(), Box<dyn Error>> {
+        self.steps.push(TransactionStep::new(op, request)?);
+        Ok(())
+    }
+
+    /// Adds a key to the transaction's watch list
+    pub fn watch_key(&mut self, key: &str) {
+        self.watch.push(key.to_string());
+    }
+}
+
+impl Sample for TransactionRequest {
+    fn sample() -> Self {
+        let mut tx = TransactionRequest::new();
+        tx.add_step(
+            OP_ADD,
+            AddRequest {
+                key: "counter1".to_string(),
+                value: 1,
+            },
+        )
+        .unwrap();
+        tx.add_step(
+            OP_PUSH,
+            ListPushRequest {
+                key: "events".to_string(),
+                value: "counter1 incremented".to_string(),
+            },
+        )
+        .unwrap();
+        tx.watch_key("counter1");
+        tx
+    }
+}
+
+/// The result of a single transaction step
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStepResult {
+    /// The operation that was performed
+    pub op: String,
+    /// The serialized response for the operation, e.g. an `AddResponse` for `OP_ADD`. Empty if
+    /// the step failed
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub response: Vec<u8>,
+    /// The reason the step failed, e.g. `OP_HASH_INCR` on a field that is not numeric. A failed
+    /// step does not roll back the steps that preceded it or prevent later steps from running
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl TransactionStepResult {
+    /// Creates the result of a step that succeeded by serializing its response
+    pub fn success<T: Serialize>(
+        op: &str,
+        response: T,
+    ) -> Result<TransactionStepResult, Box<dyn Error>> {
+        Ok(TransactionStepResult {
+            op: op.to_string(),
+            response: crate::serialize(response)?,
+            error: None,
+        })
+    }
+
+    /// Creates the result of a step that failed
+    pub fn failure(op: &str, error: &str) -> TransactionStepResult {
+        TransactionStepResult {
+            op: op.to_string(),
+            response: Vec::new(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// De-serializes the response of this step into the response type for its operation. Fails
+    /// with the step's error if the step failed
+    pub fn decode<'de, T: Deserialize<'de>>(&'de self) -> Result<T, Box<dyn Error>> {
+        match self.error {
+            Some(ref e) => Err(format!("Transaction step '{}' failed: {}", self.op, e).into()),
+            None => crate::deserialize(&self.response),
+        }
+    }
+}
+
+/// Response to a transaction. When `aborted` is true, no steps were applied and `results` is empty.
+/// Otherwise `results` contains one entry per step, in the same order as the request, and steps that
+/// failed after the transaction was committed carry an `error`
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionResponse {
+    /// Indicates the transaction was aborted because a watched key was modified
+    pub aborted: bool,
+    #[serde(default)]
+    pub results: Vec<TransactionStepResult>,
+}
+
+impl TransactionResponse {
+    /// Indicates whether the transaction was applied and every step succeeded
+    pub fn succeeded(&self) -> bool {
+        !self.aborted && self.results.iter().all(|r| r.error.is_none())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<O: KeyValueOperation>(request: O::Request) -> O::Request {
+        crate::deserialize(&crate::serialize(request).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn operations_round_trip_through_op_names() {
+        for o in Operation::ALL {
+            assert_eq!(Operation::from_op(o.op()), Some(*o));
+        }
+        assert_eq!(Operation::from_op("NoSuchOp"), None);
+    }
+
+    #[test]
+    fn marker_types_match_operations() {
+        assert_eq!(<ops::Clear as KeyValueOperation>::OP, OP_CLEAR);
+        assert_eq!(
+            <ops::KeyExists as KeyValueOperation>::OPERATION.response_type(),
+            "KeyExistsResponse"
+        );
+        let req = round_trip::<ops::Del>(DelRequest {
+            key: "k".to_string(),
+        });
+        assert_eq!(req.key, "k");
+    }
+
+    #[test]
+    fn delete_responses_default_existed() {
+        #[derive(Serialize)]
+        struct LegacyDelResponse {
+            key: String,
+        }
+        let old = crate::serialize(LegacyDelResponse {
+            key: "k".to_string(),
+        })
+        .unwrap();
+        let res: DelResponse = crate::deserialize(&old).unwrap();
+        assert!(!res.existed);
+    }
+
+    #[test]
+    fn failed_transaction_steps_report_errors() {
+        let res = TransactionResponse {
+            aborted: false,
+            results: vec![
+                TransactionStepResult::success(OP_ADD, AddResponse { value: 2 }).unwrap(),
+                TransactionStepResult::failure(OP_HASH_INCR, "field is not an integer"),
+            ],
+        };
+        let res: TransactionResponse = crate::deserialize(&crate::serialize(res).unwrap()).unwrap();
+        assert!(!res.succeeded());
+        assert_eq!(res.results[0].decode::<AddResponse>().unwrap().value, 2);
+        assert!(res.results[1].decode::<HashIncrResponse>().is_err());
+    }
+}